@@ -1,12 +1,63 @@
 //! Async drivers of the tmp117
+//!
+//! The drivers only rely on the embedded-hal-async traits and can run on any executor, embassy is not needed.
+//! The futures are woken by the i2c bus and the alert pin, so a minimal executor polls the future,
+//! sleeps until an interrupt when it is pending and polls it again:
+//!
+//! ```rust,ignore
+//! use core::{
+//!     future::Future,
+//!     pin::pin,
+//!     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+//! };
+//!
+//! fn block_on<F: Future>(fut: F) -> F::Output {
+//!     // The interrupts of the bus and the pin wake the core, so the waker has nothing to do
+//!     fn clone(_: *const ()) -> RawWaker {
+//!         RawWaker::new(core::ptr::null(), &VTABLE)
+//!     }
+//!     fn noop(_: *const ()) {}
+//!     static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+//!     let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+//!     let mut cx = Context::from_waker(&waker);
+//!
+//!     let mut fut = pin!(fut);
+//!     loop {
+//!         if let Poll::Ready(val) = fut.as_mut().poll(&mut cx) {
+//!             return val;
+//!         }
+//!         cortex_m::asm::wfi();
+//!     }
+//! }
+//!
+//! let mut tmp = Tmp117::new_alert(i2c, 0x48, alert_pin);
+//! block_on(tmp.continuous(Default::default(), |mut t| async move {
+//!     let temp = t.wait_temp().await?;
+//!     Ok(())
+//! }))?;
+//! ```
+//!
+//! Without an alert pin, waiting polls the configuration register over the bus until the data is ready.
+//! Without an async i2c bus, see [ContinuousHandler::poll_data_ready](crate::ContinuousHandler::poll_data_ready)
 
-use core::{convert::Infallible, future::Future};
+use core::{convert::Infallible, future::Future, marker::PhantomData, ops::ControlFlow};
 
-use device_register_async::{EditRegister, ReadRegister, WriteRegister};
-use embedded_hal::{digital::ErrorType, i2c::SevenBitAddress};
+use device_register::Register;
+use device_register_async::{EditRegister, ReadRegister, RegisterInterface, WriteRegister};
+use embedded_hal::{
+    digital::ErrorType,
+    i2c::{ErrorKind, NoAcknowledgeSource, SevenBitAddress},
+};
 use embedded_hal_async::{delay::DelayNs, digital::Wait, i2c::I2c};
 
-use crate::{register::*, Alert, ContinuousConfig, Error, Id, CELCIUS_CONVERSION};
+use crate::{
+    check_eeprom, decode_temperature, filter::IirFilter, heat_on, register::*, Alert, AlertConfig,
+    AlertLimits, AlertPinConfig, Celsius, ConfigImage, ConfigSnapshot, ContinuousConfig,
+    DeviceConfig, Diagnosis, EepromWrite, Error, Event, Id, Measurement, ReadDelay, UserStore,
+    EEPROM_POLL_US, EEPROM_TIMEOUT_US,
+};
+#[cfg(feature = "clock")]
+use crate::{Clock, Timestamped};
 
 use self::tmp117_ll::Tmp117LL;
 pub mod tmp117_ll;
@@ -58,45 +109,77 @@ impl<P> AlertPin<P> {
     }
 }
 
+/// Wait for the pin to reach the active level of the polarity
+async fn wait_active<P: Wait>(pin: &mut P, polarity: Polarity) -> Result<(), P::Error> {
+    match polarity {
+        Polarity::ActiveLow => pin.wait_for_low().await,
+        Polarity::ActiveHigh => pin.wait_for_high().await,
+    }
+}
+
+/// A sensor returning a value on each read, allows application code to be generic over the sensor used
+pub trait SensorRead {
+    /// The value read
+    type Value;
+
+    /// The error returned by the sensor
+    type Error;
+
+    /// Wait for the next value of the sensor and return it
+    fn read(&mut self) -> impl Future<Output = Result<Self::Value, Self::Error>>;
+}
+
 /// The TMP117 driver. Note that the alert pin is optional, but it is recommended to pass it if possible
 /// If the alert pin is `None`, the driver will poll the config register instead of waiting for the pin.
-pub struct Tmp117<T, E, P> {
+pub struct Tmp117<T, E, P, PE> {
     tmp_ll: Tmp117LL<T, E>,
     alert: Option<AlertPin<P>>,
+    data_ready_pin: Option<Polarity>,
+    polarity: Polarity,
+    eeprom_programming: bool,
+    pe: PhantomData<PE>,
 }
 
-impl<T, E> Tmp117<T, E, DummyWait>
+impl<T, E> Tmp117<T, E, DummyWait, Infallible>
 where
     T: I2c<SevenBitAddress, Error = E>,
     E: embedded_hal::i2c::Error + Copy,
 {
     /// Create a new tmp117 from a i2c bus
     /// # Warning
-    /// You should use the `new_with_alert` function instead if possible
+    /// You should use the [new_alert](Tmp117::new_alert) function instead if possible, the pin error type is inferred from the pin
     /// It seems the tmp117 doesn't always set the data ready flag, so you should add a timeout when using `oneshot` wihout an alert pin.
     /// See [this](https://e2e.ti.com/support/sensors-group/sensors/f/sensors-forum/909104/tmp117-polling-the-data-ready-flag-seems-to-clear-it-inadvertently-when-using-1-shot-mode)
     /// and [this](https://e2e.ti.com/support/sensors-group/sensors/f/sensors-forum/1019457/tmp117-data_ready-flag-cleared-incorrectly-if-data-becomes-ready-during-read-of-configuration-register)
     /// for more information.
     /// TODO: Pass and use delay instead of polling to fix this
-    pub fn new(i2c: T, addr: u8) -> Tmp117<T, E, DummyWait> {
-        Tmp117::<T, E, DummyWait> {
+    pub fn new(i2c: T, addr: u8) -> Tmp117<T, E, DummyWait, Infallible> {
+        Tmp117::<T, E, DummyWait, Infallible> {
             tmp_ll: Tmp117LL::new(i2c, addr),
             alert: None,
+            data_ready_pin: None,
+            polarity: Polarity::ActiveLow,
+            eeprom_programming: false,
+            pe: PhantomData,
         }
     }
 }
 
-impl<T, E, P> Tmp117<T, E, P>
+impl<T, E, P, PE> Tmp117<T, E, P, PE>
 where
     T: I2c<SevenBitAddress, Error = E>,
     E: embedded_hal::i2c::Error + Copy,
-    P: Wait,
+    P: Wait<Error = PE>,
 {
     /// Create a new tmp117 from a i2c bus and alert pin
     pub fn new_alert(i2c: T, addr: u8, alert: P) -> Self {
         Self {
             tmp_ll: Tmp117LL::new(i2c, addr),
             alert: Some(AlertPin::Unkown(alert)),
+            data_ready_pin: None,
+            polarity: Polarity::ActiveLow,
+            eeprom_programming: false,
+            pe: PhantomData,
         }
     }
 
@@ -105,100 +188,311 @@ where
         Self {
             tmp_ll,
             alert: Some(AlertPin::Unkown(alert)),
+            data_ready_pin: None,
+            polarity: Polarity::ActiveLow,
+            eeprom_programming: false,
+            pe: PhantomData,
         }
     }
 
+    /// Create a new tmp117 from a i2c bus and alert pin and verify the device ID.
+    /// Set `accept_any_id` for register compatible parts reporting another ID, the ID is still read and returned
+    pub async fn new_alert_checked(
+        i2c: T,
+        addr: u8,
+        alert: P,
+        accept_any_id: bool,
+    ) -> Result<(Self, Id), Error<E, PE>> {
+        let mut tmp = Self::new_alert(i2c, addr, alert);
+        let id = tmp.id().await?.check(accept_any_id)?;
+        Ok((tmp, id))
+    }
+
+    /// Create a new tmp117 from a i2c bus and alert pin without resetting or reconfiguring the device.
+    /// Returns the driver with the conversion mode the device is currently in,
+    /// useful to take over a device already configured by a bootloader for example
+    pub async fn adopt(i2c: T, addr: u8, alert: P) -> Result<(Self, ConversionMode), Error<E, PE>> {
+        let mut tmp = Self::new_alert(i2c, addr, alert);
+        let config: Configuration = tmp.tmp_ll.read().await?;
+        tmp.detect_alert_pin(&config);
+        Ok((tmp, config.mode()))
+    }
+
+    /// Update the known state of the alert pin from the configuration read on the device
+    fn detect_alert_pin(&mut self, config: &Configuration) {
+        // The pin state is only known if it has the polarity used by the driver
+        let known = config.polarity() == self.polarity;
+        self.alert = self.alert.take().map(|v| match (known, config.dr_alert()) {
+            (true, AlertPinSelect::Alert) => AlertPin::Alert(v.unwrap()),
+            (true, AlertPinSelect::DataReady) => AlertPin::DataReady(v.unwrap()),
+            (false, _) => AlertPin::Unkown(v.unwrap()),
+        });
+    }
+
     /// Returns the ID of the device
-    pub async fn id(&mut self) -> Result<Id, Error<E>> {
+    pub async fn id(&mut self) -> Result<Id, Error<E, PE>> {
         let id: DeviceID = self.tmp_ll.read().await?;
-        Ok(Id {
-            device: id.device_id().into(),
-            revision: id.revision().into(),
+        Ok(id.into())
+    }
+
+    /// Returns the ID of the device, or `None` if no device acknowledged the address.
+    /// Other bus errors are still returned, useful to scan for devices
+    pub async fn probe(&mut self) -> Result<Option<Id>, Error<E, PE>> {
+        match self.id().await {
+            Ok(id) => Ok(Some(id)),
+            Err(Error::Bus(e))
+                if matches!(
+                    e.kind(),
+                    ErrorKind::NoAcknowledge(
+                        NoAcknowledgeSource::Address | NoAcknowledgeSource::Unknown
+                    )
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read the device ID and tell if the device is missing, is not a TMP117 or if the bus failed, in a single call
+    pub async fn diagnose(&mut self) -> Diagnosis {
+        Diagnosis::from_id(self.id().await)
+    }
+
+    /// Read a snapshot of the configuration, limits and offset of the device
+    pub async fn snapshot(&mut self) -> Result<ConfigSnapshot, Error<E, PE>> {
+        let configuration: Configuration = self.tmp_ll.read().await?;
+        let high: HighLimit = self.tmp_ll.read().await?;
+        let low: LowLimit = self.tmp_ll.read().await?;
+        let offset: TemperatureOffset = self.tmp_ll.read().await?;
+        Ok(ConfigSnapshot {
+            configuration,
+            high_limit: decode_temperature(high.into()).into(),
+            low_limit: decode_temperature(low.into()).into(),
+            offset: decode_temperature(offset.into()).into(),
         })
     }
 
-    async fn wait_eeprom(&mut self) -> Result<(), Error<E>> {
-        let mut configuration: Configuration = self.tmp_ll.read().await?;
-        while configuration.eeprom_busy() {
-            configuration = self.tmp_ll.read().await?;
+    /// Read the configuration, limits and offset, and the user eeprom if `with_eeprom` is set,
+    /// to copy them to another device with [import_config](Self::import_config)
+    pub async fn export_config(&mut self, with_eeprom: bool) -> Result<DeviceConfig, Error<E, PE>> {
+        let user_eeprom = if with_eeprom {
+            Some(self.read_eeprom().await?)
+        } else {
+            None
+        };
+        Ok(DeviceConfig {
+            configuration: self.tmp_ll.read().await?,
+            high_limit: self.tmp_ll.read().await?,
+            low_limit: self.tmp_ll.read().await?,
+            offset: self.tmp_ll.read().await?,
+            user_eeprom,
+        })
+    }
+
+    /// Write a configuration read from another device with [export_config](Self::export_config).
+    /// The user eeprom is written first if present, which needs the device to be in shutdown,
+    /// returns [Error::BusyConverting] otherwise
+    pub async fn import_config<D>(
+        &mut self,
+        config: &DeviceConfig,
+        delay: &mut D,
+    ) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        self.check_eeprom_locked().await?;
+        if let Some(values) = config.user_eeprom {
+            self.write_eeprom(values, delay).await?;
         }
+        self.tmp_ll.write(config.high_limit).await?;
+        self.tmp_ll.write(config.low_limit).await?;
+        self.tmp_ll.write(config.offset).await?;
+
+        let configuration =
+            Configuration::try_from(u16::from(config.configuration) & CONFIGURATION_WRITABLE_MASK)
+                .map_err(|_| Error::InvalidData)?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| *r = configuration)
+            .await?;
 
+        // The pin configuration was overwritten
+        self.alert = self.alert.take().map(|v| AlertPin::Unkown(v.unwrap()));
+        if let Some(polarity) = self.data_ready_pin {
+            self.use_pin_as_data_ready(polarity).await?;
+        }
         Ok(())
     }
 
-    async fn read_temp_raw(&mut self) -> Result<f32, Error<E>> {
-        let temp: Temperature = self.tmp_ll.read().await?;
-
-        // Convert to i16 for two complements
-        let val = (u16::from(temp) as i16) as f32 * CELCIUS_CONVERSION;
-        Ok(val)
+    /// Returns the conversion period in milliseconds of the average and conversion currently configured,
+    /// useful after [adopt](Self::adopt) to know the cadence of a device configured by someone else.
+    /// Note that reading the configuration clears the data ready flag
+    pub async fn current_conversion_period_ms(&mut self) -> Result<u32, Error<E, PE>> {
+        let config: Configuration = self.tmp_ll.read().await?;
+        Ok(conversion_period_ms(config.conversion(), config.average()))
     }
 
-    async fn check_alert(&mut self) -> Result<Alert, Error<E>> {
+    /// Returns an upper bound of the time in milliseconds until the next data is ready,
+    /// based on the current mode, average and conversion. Returns 0 if the data is already ready.
+    /// In shutdown, returns the time a oneshot conversion would take.
+    /// Note that reading the configuration clears the data ready flag
+    pub async fn time_to_data_ready(&mut self) -> Result<u32, Error<E, PE>> {
         let config: Configuration = self.tmp_ll.read().await?;
-        if config.high_alert() && config.low_alert() {
-            Ok(Alert::HighLow)
-        } else if config.high_alert() {
-            Ok(Alert::High)
-        } else if config.low_alert() {
-            Ok(Alert::Low)
-        } else {
-            Ok(Alert::None)
-        }
-    }
-
-    async fn set_alert(&mut self) -> Result<(), Error<E>> {
-        // If we have a pin
-        if let Some(p) = &mut self.alert {
-            // If in alert, just use it
-            if let AlertPin::Alert(_) = p {
-            } else {
-                // If not, set it to alert
-                self.tmp_ll
-                    .edit(|r: &mut Configuration| {
-                        r.set_dr_alert(AlertPinSelect::Alert);
-                        r.set_polarity(Polarity::ActiveLow);
-                    })
-                    .await?;
+        if config.data_ready() {
+            return Ok(0);
+        }
+
+        let period = match config.mode() {
+            ConversionMode::Continuous => {
+                conversion_period_ms(config.conversion(), config.average())
+            }
+            ConversionMode::OneShot | ConversionMode::Shutdown => {
+                conversion_period_ms(Conversion::Ms15_5, config.average())
+            }
+        };
+        Ok(period)
+    }
+
+    /// Returns true if the eeprom is busy, either programming or loading at power-up.
+    /// Uses the flag of the [Configuration] register, which is the one the driver relies on.
+    /// The [EEPROM] register flag mirrors it
+    pub async fn eeprom_busy(&mut self) -> Result<bool, Error<E, PE>> {
+        let configuration: Configuration = self.tmp_ll.read().await?;
+        Ok(configuration.eeprom_busy())
+    }
+
+    async fn wait_eeprom<D>(&mut self, delay: &mut D) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        for _ in 0..EEPROM_TIMEOUT_US / EEPROM_POLL_US {
+            if !self.eeprom_busy().await? {
+                return Ok(());
             }
-            self.alert = self.alert.take().map(|v| AlertPin::Alert(v.unwrap()));
+            delay.delay_us(EEPROM_POLL_US).await;
+        }
+        Err(Error::EepromTimeout)
+    }
+
+    async fn read_temp_raw(&mut self) -> Result<f32, Error<E, PE>> {
+        let raw = self.tmp_ll.read_temperature_raw().await?;
+        Ok(decode_temperature(raw as u16))
+    }
+
+    async fn check_alert(&mut self) -> Result<Alert, Error<E, PE>> {
+        let config: Configuration = self.tmp_ll.read().await?;
+        Ok(Alert::from_config(&config))
+    }
+
+    /// Permanently use the alert pin as data ready with the given polarity.
+    /// Waiting for alerts then polls the configuration register instead of selecting the pin as alert,
+    /// so waiting for data never needs to select the pin function again.
+    /// Returns [Error::NoAlertPin] if the driver has no alert pin
+    pub async fn use_pin_as_data_ready(&mut self, polarity: Polarity) -> Result<(), Error<E, PE>> {
+        if self.alert.is_none() {
+            return Err(Error::NoAlertPin);
+        }
+
+        self.tmp_ll
+            .edit(|r: &mut Configuration| {
+                r.set_dr_alert(AlertPinSelect::DataReady);
+                r.set_polarity(polarity);
+            })
+            .await?;
+        self.alert = self.alert.take().map(|v| AlertPin::DataReady(v.unwrap()));
+        self.data_ready_pin = Some(polarity);
+        self.polarity = polarity;
+        Ok(())
+    }
+
+    /// Allow the setters to write while the eeprom is unlocked, which also programs the eeprom.
+    /// By default, the setters and the methods changing the conversion mode return [Error::EepromUnlocked]
+    /// to avoid wearing the eeprom by accident
+    pub fn allow_eeprom_programming(&mut self, allow: bool) {
+        self.eeprom_programming = allow;
+    }
+
+    async fn check_eeprom_locked(&mut self) -> Result<(), Error<E, PE>> {
+        if self.eeprom_programming {
+            return Ok(());
+        }
+
+        let eeprom: EEPROM = self.tmp_ll.read().await?;
+        if eeprom.unlock() {
+            return Err(Error::EepromUnlocked);
         }
         Ok(())
     }
 
-    async fn set_data_ready(&mut self) -> Result<(), Error<E>> {
-        // If we have a pin
-        if let Some(p) = &mut self.alert {
-            // If in data ready, just use it
-            if let AlertPin::DataReady(_) = p {
-            } else {
-                // If not, set it to data ready
-                self.tmp_ll
-                    .edit(|r: &mut Configuration| {
-                        r.set_dr_alert(AlertPinSelect::DataReady);
-                        r.set_polarity(Polarity::ActiveLow);
-                    })
-                    .await?;
-            }
-            self.alert = self.alert.take().map(|v| AlertPin::DataReady(v.unwrap()));
+    /// Select what the alert pin reflects, either the alert flags or the data ready flag.
+    /// The polarity is set to the one of [set_alert_pin_config](Self::set_alert_pin_config), active low by default,
+    /// and the pin is no longer kept as data ready
+    pub async fn set_alert_pin_select(
+        &mut self,
+        select: AlertPinSelect,
+    ) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.select_alert_pin(select).await
+    }
+
+    async fn select_alert_pin(&mut self, select: AlertPinSelect) -> Result<(), Error<E, PE>> {
+        let polarity = self.polarity;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| {
+                r.set_dr_alert(select);
+                r.set_polarity(polarity);
+            })
+            .await?;
+        self.data_ready_pin = None;
+        self.alert = self.alert.take().map(|v| match select {
+            AlertPinSelect::Alert => AlertPin::Alert(v.unwrap()),
+            AlertPinSelect::DataReady => AlertPin::DataReady(v.unwrap()),
+        });
+        Ok(())
+    }
+
+    /// Returns what the alert pin currently reflects
+    pub async fn get_alert_pin_select(&mut self) -> Result<AlertPinSelect, Error<E, PE>> {
+        let config: Configuration = self.tmp_ll.read().await?;
+        Ok(config.dr_alert())
+    }
+
+    async fn set_alert(&mut self) -> Result<(), Error<E, PE>> {
+        // The pin is kept as data ready
+        if self.data_ready_pin.is_some() {
+            return Ok(());
+        }
+
+        // If we have a pin that is not in alert, set it to alert
+        if let Some(AlertPin::Unkown(_) | AlertPin::DataReady(_)) = self.alert {
+            self.select_alert_pin(AlertPinSelect::Alert).await?;
         }
         Ok(())
     }
 
-    async fn wait_for_data(&mut self) -> Result<(), Error<E>> {
-        // If we have a pin
-        if let Some(AlertPin::DataReady(p)) = &mut self.alert {
-            loop {
-                // Wait for it to go low
-                p.wait_for_low().await.map_err(|_| Error::AlertPin)?;
+    async fn set_data_ready(&mut self) -> Result<(), Error<E, PE>> {
+        // If we have a pin that is not in data ready, set it to data ready
+        if let Some(AlertPin::Unkown(_) | AlertPin::Alert(_)) = self.alert {
+            self.select_alert_pin(AlertPinSelect::DataReady).await?;
+        }
+        Ok(())
+    }
 
-                // Clear flag in register
-                let config: Configuration = self.tmp_ll.read().await?;
+    /// Forget the state of the pin after an error so the next wait selects the pin function again.
+    /// A pin kept as data ready is not reconfigured by the driver, so its state stays valid
+    fn pin_error(&mut self, e: PE) -> Error<E, PE> {
+        if self.data_ready_pin.is_none() {
+            self.alert = self.alert.take().map(|v| AlertPin::Unkown(v.unwrap()));
+        }
+        Error::AlertPin(e)
+    }
 
-                // Validate that the data is ready
-                if config.data_ready() {
-                    break;
-                }
+    async fn wait_for_data(&mut self) -> Result<(), Error<E, PE>> {
+        // Reading the temperature clears the data ready pin, and every caller reads it right after.
+        // The configuration is not read to check the flag, polling it can clear it inadvertently, see the errata in `new`
+        if let Some(AlertPin::DataReady(p)) = &mut self.alert {
+            if let Err(e) = wait_active(p, self.polarity).await {
+                return Err(self.pin_error(e));
             }
         } else {
             // Loop while the alert is not ok
@@ -212,9 +506,11 @@ where
         Ok(())
     }
 
-    async fn wait_for_alert(&mut self) -> Result<Alert, Error<E>> {
+    async fn wait_for_alert(&mut self) -> Result<Alert, Error<E, PE>> {
         if let Some(AlertPin::Alert(p)) = &mut self.alert {
-            p.wait_for_low().await.map_err(|_| Error::AlertPin)?;
+            if let Err(e) = wait_active(p, self.polarity).await {
+                return Err(self.pin_error(e));
+            }
             self.check_alert().await
         } else {
             loop {
@@ -228,46 +524,78 @@ where
         }
     }
 
-    async fn set_continuous(
+    async fn apply_config(
         &mut self,
-        config: ContinuousConfig,
-    ) -> Result<ContinuousHandler<T, E, P>, Error<E>> {
-        self.set_data_ready().await?;
+        config: &ContinuousConfig,
+        mode: ConversionMode,
+    ) -> Result<(), Error<E, PE>> {
         if let Some(val) = config.high {
-            let high: HighLimit = ((val / CELCIUS_CONVERSION) as u16).into();
-            self.tmp_ll.write(high).await?;
+            self.tmp_ll.write(HighLimit::try_from_celsius(val)?).await?;
         }
         if let Some(val) = config.low {
-            let low: LowLimit = ((val / CELCIUS_CONVERSION) as u16).into();
-            self.tmp_ll.write(low).await?;
+            self.tmp_ll.write(LowLimit::try_from_celsius(val)?).await?;
         }
         if let Some(val) = config.offset {
-            let off: TemperatureOffset = ((val / CELCIUS_CONVERSION) as u16).into();
-            self.tmp_ll.write(off).await?;
+            self.tmp_ll
+                .write(TemperatureOffset::try_from_celsius(val)?)
+                .await?;
         }
 
+        let polarity = self.polarity;
         self.tmp_ll
             .edit(|r: &mut Configuration| {
-                r.set_mode(ConversionMode::Continuous);
+                r.set_mode(mode);
                 r.set_average(config.average);
                 r.set_conversion(config.conversion);
+                r.set_trigger_mode(config.trigger_mode);
+                if let Some(select) = config.pin_select {
+                    r.set_dr_alert(select);
+                    r.set_polarity(polarity);
+                }
             })
             .await?;
+
+        if let Some(select) = config.pin_select {
+            self.data_ready_pin = None;
+            self.alert = self.alert.take().map(|v| match select {
+                AlertPinSelect::Alert => AlertPin::Alert(v.unwrap()),
+                AlertPinSelect::DataReady => AlertPin::DataReady(v.unwrap()),
+            });
+        }
+        Ok(())
+    }
+
+    async fn set_continuous(
+        &mut self,
+        config: ContinuousConfig,
+    ) -> Result<ContinuousHandler<T, E, P, PE>, Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.set_data_ready().await?;
+        self.apply_config(&config, ConversionMode::Continuous)
+            .await?;
         Ok(ContinuousHandler { tmp117: self })
     }
 
-    async fn set_oneshot(&mut self, average: Average) -> Result<(), Error<E>> {
+    async fn set_oneshot(
+        &mut self,
+        average: Average,
+        conversion: Option<Conversion>,
+    ) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
         self.set_data_ready().await?;
         self.tmp_ll
             .edit(|r: &mut Configuration| {
                 r.set_mode(ConversionMode::OneShot);
                 r.set_average(average);
+                if let Some(conversion) = conversion {
+                    r.set_conversion(conversion);
+                }
             })
             .await?;
         Ok(())
     }
 
-    async fn set_shutdown(&mut self) -> Result<(), Error<E>> {
+    async fn set_shutdown(&mut self) -> Result<(), Error<E, PE>> {
         self.tmp_ll
             .edit(|r: &mut Configuration| {
                 r.set_mode(ConversionMode::Shutdown);
@@ -276,8 +604,196 @@ where
         Ok(())
     }
 
-    /// Resets the device and put it in shutdown
-    pub async fn reset<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    /// Returns [Error::SampleRateTooHigh] if the configured conversion cycle is shorter than the time
+    /// the caller needs to service each data, in which case samples would be silently missed
+    pub async fn warn_if_too_fast(&mut self, min_service_ms: u32) -> Result<(), Error<E, PE>> {
+        let config: Configuration = self.tmp_ll.read().await?;
+        if conversion_period_ms(config.conversion(), config.average()) < min_service_ms {
+            return Err(Error::SampleRateTooHigh);
+        }
+        Ok(())
+    }
+
+    /// Set the high limit from the raw two's complement register value, one lsb is [CELCIUS_CONVERSION](crate::CELCIUS_CONVERSION)
+    pub async fn set_high_limit_raw(&mut self, raw: i16) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.tmp_ll.write(HighLimit::from(raw as u16)).await?;
+        Ok(())
+    }
+
+    /// Returns the raw two's complement value of the high limit, one lsb is [CELCIUS_CONVERSION](crate::CELCIUS_CONVERSION)
+    pub async fn high_limit_raw(&mut self) -> Result<i16, Error<E, PE>> {
+        let high: HighLimit = self.tmp_ll.read().await?;
+        Ok(u16::from(high) as i16)
+    }
+
+    /// Set the low limit from the raw two's complement register value, one lsb is [CELCIUS_CONVERSION](crate::CELCIUS_CONVERSION)
+    pub async fn set_low_limit_raw(&mut self, raw: i16) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.tmp_ll.write(LowLimit::from(raw as u16)).await?;
+        Ok(())
+    }
+
+    /// Returns the raw two's complement value of the low limit, one lsb is [CELCIUS_CONVERSION](crate::CELCIUS_CONVERSION)
+    pub async fn low_limit_raw(&mut self) -> Result<i16, Error<E, PE>> {
+        let low: LowLimit = self.tmp_ll.read().await?;
+        Ok(u16::from(low) as i16)
+    }
+
+    /// Returns the raw value of the configuration register, before decoding
+    pub async fn read_config_raw(&mut self) -> Result<u16, Error<E, PE>> {
+        let val = self.tmp_ll.read_raw(Configuration::ADDRESS).await?;
+        Ok(val)
+    }
+
+    /// Writes the raw value to the configuration register.
+    /// Only the bits of [CONFIGURATION_WRITABLE_MASK] are written, the read only bits are ignored
+    pub async fn write_config_raw(&mut self, value: u16) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        let config = Configuration::try_from(value & CONFIGURATION_WRITABLE_MASK)
+            .map_err(|_| Error::InvalidData)?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| *r = config)
+            .await?;
+        Ok(())
+    }
+
+    /// Configure the device to only drive the alert pin in thermal mode, the pin goes active above `high`
+    /// and back inactive below `high - hysteresis`. The device is left converting in continuous mode.
+    /// Use [wait_thermal_alert](Self::wait_thermal_alert) to wait on the pin without reading the temperature over i2c,
+    /// the bus stays idle and the host can sleep until the pin changes, which saves most of the power of the system
+    pub async fn arm_thermal_alert(
+        &mut self,
+        high: Celsius,
+        hysteresis: Celsius,
+        polarity: Polarity,
+    ) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.tmp_ll
+            .write(HighLimit::try_from_celsius(high.0)?)
+            .await?;
+        self.tmp_ll
+            .write(LowLimit::try_from_celsius(high.0 - hysteresis.0)?)
+            .await?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| {
+                r.set_mode(ConversionMode::Continuous);
+                r.set_trigger_mode(TriggerMode::Thermal);
+                r.set_dr_alert(AlertPinSelect::Alert);
+                r.set_polarity(polarity);
+            })
+            .await?;
+        self.data_ready_pin = None;
+        self.polarity = polarity;
+        self.alert = self.alert.take().map(|v| AlertPin::Alert(v.unwrap()));
+        Ok(())
+    }
+
+    /// Wait for the alert pin to go active after [arm_thermal_alert](Self::arm_thermal_alert),
+    /// only the configuration is read to get the polarity. Without an alert pin, the high alert flag is polled instead
+    pub async fn wait_thermal_alert(&mut self) -> Result<(), Error<E, PE>> {
+        let config: Configuration = self.tmp_ll.read().await?;
+        match &mut self.alert {
+            Some(p) => {
+                let p = match p {
+                    AlertPin::Unkown(p) | AlertPin::DataReady(p) | AlertPin::Alert(p) => p,
+                };
+                if let Err(e) = wait_active(p, config.polarity()).await {
+                    return Err(self.pin_error(e));
+                }
+                Ok(())
+            }
+            None => loop {
+                let config: Configuration = self.tmp_ll.read().await?;
+                if config.high_alert() {
+                    return Ok(());
+                }
+            },
+        }
+    }
+
+    /// Read the alert pin selection, polarity and trigger mode in a single transaction.
+    /// Note that reading the configuration clears the alert and data ready flags
+    pub async fn read_alert_config(&mut self) -> Result<AlertConfig, Error<E, PE>> {
+        let config: Configuration = self.tmp_ll.read().await?;
+        Ok(AlertConfig {
+            pin_select: config.dr_alert(),
+            polarity: config.polarity(),
+            trigger_mode: config.trigger_mode(),
+        })
+    }
+
+    /// Write the alert pin selection, polarity and trigger mode with a single read-modify-write of the configuration.
+    /// The pin is no longer kept as data ready
+    pub async fn write_alert_config(&mut self, alert: &AlertConfig) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| {
+                r.set_dr_alert(alert.pin_select);
+                r.set_polarity(alert.polarity);
+                r.set_trigger_mode(alert.trigger_mode);
+            })
+            .await?;
+        self.data_ready_pin = None;
+        self.polarity = alert.polarity;
+        self.alert = self.alert.take().map(|v| match alert.pin_select {
+            AlertPinSelect::Alert => AlertPin::Alert(v.unwrap()),
+            AlertPinSelect::DataReady => AlertPin::DataReady(v.unwrap()),
+        });
+        Ok(())
+    }
+
+    /// Set the polarity of the alert pin to match the circuit described, see [AlertPinConfig].
+    /// Returns [Error::UnreachablePolarity] if the circuit cannot reach the active level.
+    /// The driver keeps the polarity, it is written again when selecting the pin function and used to wait on the pin
+    pub async fn set_alert_pin_config(
+        &mut self,
+        config: &AlertPinConfig,
+    ) -> Result<(), Error<E, PE>> {
+        let polarity = config.polarity().ok_or(Error::UnreachablePolarity)?;
+        self.check_eeprom_locked().await?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| r.set_polarity(polarity))
+            .await?;
+        self.polarity = polarity;
+        let config: Configuration = self.tmp_ll.read().await?;
+        self.detect_alert_pin(&config);
+        Ok(())
+    }
+
+    /// Write the high and low alert limits
+    pub async fn set_alert_limits(&mut self, limits: AlertLimits) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.tmp_ll.write(limits.high).await?;
+        self.tmp_ll.write(limits.low).await?;
+        Ok(())
+    }
+
+    /// Write a configuration image built with [ConfigImage::build]. The read only bits of the configuration are ignored
+    pub async fn apply_image(&mut self, image: &ConfigImage) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        self.tmp_ll.write(HighLimit::from(image.high_limit)).await?;
+        self.tmp_ll.write(LowLimit::from(image.low_limit)).await?;
+        self.tmp_ll
+            .write(TemperatureOffset::from(image.offset))
+            .await?;
+
+        let configuration =
+            Configuration::try_from(image.configuration & CONFIGURATION_WRITABLE_MASK)
+                .map_err(|_| Error::InvalidData)?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| *r = configuration)
+            .await?;
+        self.detect_alert_pin(&configuration);
+        if let Some(polarity) = self.data_ready_pin {
+            self.use_pin_as_data_ready(polarity).await?;
+        }
+        Ok(())
+    }
+
+    /// Resets the device and put it in shutdown.
+    /// The alert pin selection reloaded from the eeprom is read back, so it is kept if it was saved
+    pub async fn reset<D>(&mut self, delay: &mut D) -> Result<(), Error<E, PE>>
     where
         D: DelayNs,
     {
@@ -287,25 +803,186 @@ where
             })
             .await?;
         delay.delay_ms(2).await;
+
+        // The pin configuration was reloaded from the eeprom
+        let config: Configuration = self.tmp_ll.read().await?;
+        self.detect_alert_pin(&config);
+        if let Some(polarity) = self.data_ready_pin {
+            self.use_pin_as_data_ready(polarity).await?;
+        }
         self.set_shutdown().await
     }
 
-    /// Write data to user eeprom. Note that this is blocking because we wait for write on the eeprom to complete
-    pub async fn write_eeprom(&mut self, values: [u16; 3]) -> Result<(), Error<E>> {
-        self.wait_eeprom().await?;
-        self.tmp_ll.write(UEEPROM1::from(values[0])).await?;
+    /// Resets the device and return the first valid temperature in celsius.
+    /// Reading right after a reset returns the -256 °C power up value until the first conversion,
+    /// so this waits for the reset and the eeprom load, then does a oneshot with the average loaded from the eeprom.
+    /// The device is in shutdown afterwards
+    pub async fn wait_ready_after_reset<D>(&mut self, delay: &mut D) -> Result<f32, Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        self.reset(delay).await?;
+        self.wait_eeprom(delay).await?;
+        let config: Configuration = self.tmp_ll.read().await?;
+        self.oneshot(config.average()).await
+    }
+
+    /// Apply the settings of the profile without changing the conversion mode, only the registers that differ are written.
+    /// Allows to switch cheaply between profiles, a fast and a slow one for example
+    pub async fn apply_profile(&mut self, profile: &ContinuousConfig) -> Result<(), Error<E, PE>> {
+        self.check_eeprom_locked().await?;
+        if let Some(val) = profile.high {
+            let new = HighLimit::try_from_celsius(val)?;
+            let high: HighLimit = self.tmp_ll.read().await?;
+            if high != new {
+                self.tmp_ll.write(new).await?;
+            }
+        }
+        if let Some(val) = profile.low {
+            let new = LowLimit::try_from_celsius(val)?;
+            let low: LowLimit = self.tmp_ll.read().await?;
+            if low != new {
+                self.tmp_ll.write(new).await?;
+            }
+        }
+        if let Some(val) = profile.offset {
+            let new = TemperatureOffset::try_from_celsius(val)?;
+            let offset: TemperatureOffset = self.tmp_ll.read().await?;
+            if offset != new {
+                self.tmp_ll.write(new).await?;
+            }
+        }
+
+        let current: Configuration = self.tmp_ll.read().await?;
+        let mut config = current;
+        config.set_average(profile.average);
+        config.set_conversion(profile.conversion);
+        config.set_trigger_mode(profile.trigger_mode);
+        if config != current {
+            self.tmp_ll.write_register(&config).await?;
+        }
+        Ok(())
+    }
+
+    /// Recover to a known good state, after detecting a corruption for example.
+    /// Resets the device, verifies its ID and applies the config, leaving the device in shutdown
+    pub async fn recover<D>(
+        &mut self,
+        delay: &mut D,
+        config: ContinuousConfig,
+    ) -> Result<Id, Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        self.check_eeprom_locked().await?;
+        self.reset(delay).await?;
+        let id = self.id().await?.check(false)?;
+        self.apply_config(&config, ConversionMode::Shutdown).await?;
+        Ok(id)
+    }
+
+    /// Write data to user eeprom, the delay is used between the polls of the busy flag.
+    /// The device must be in shutdown, returns [Error::BusyConverting] otherwise
+    pub async fn write_eeprom<D>(
+        &mut self,
+        values: [u16; 3],
+        delay: &mut D,
+    ) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        self.resume_eeprom_write(&mut EepromWrite::new(values), delay)
+            .await
+    }
+
+    /// Write data to the [UEEPROM2] and [UEEPROM3] words of the user eeprom, [UEEPROM1] is never written to keep the NIST ID.
+    /// The device must be in shutdown, returns [Error::BusyConverting] otherwise
+    pub async fn write_user_words<D>(
+        &mut self,
+        values: [u16; 2],
+        delay: &mut D,
+    ) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        self.resume_eeprom_write(&mut EepromWrite::user_words(values), delay)
+            .await
+    }
 
-        self.wait_eeprom().await?;
-        self.tmp_ll.write(UEEPROM2::from(values[1])).await?;
+    /// Write the remaining words of the eeprom write, the progress is updated after each word.
+    /// The eeprom is unlocked for the write and locked again once the last word is programmed.
+    /// Returns [Error::BusyConverting] if the device is not in shutdown.
+    /// If the future is dropped or an error occurs, the eeprom may stay unlocked, calling it again resumes the write.
+    /// Returns [Error::EepromTimeout] if the eeprom stays busy
+    pub async fn resume_eeprom_write<D>(
+        &mut self,
+        write: &mut EepromWrite,
+        delay: &mut D,
+    ) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        // Programming while converting can interact with the eeprom controller
+        let config: Configuration = self.tmp_ll.read().await?;
+        if config.mode() != ConversionMode::Shutdown {
+            return Err(Error::BusyConverting);
+        }
 
-        self.wait_eeprom().await?;
-        self.tmp_ll.write(UEEPROM3::from(values[2])).await?;
+        // Writes to the user eeprom registers only program the eeprom while it is unlocked
+        self.tmp_ll
+            .edit(|r: &mut EEPROM| r.set_unlock(true))
+            .await?;
+        while !write.is_done() {
+            self.wait_eeprom(delay).await?;
+            let value = write.values[write.written];
+            match write.written {
+                0 => self.tmp_ll.write(UEEPROM1::from(value)).await?,
+                1 => self.tmp_ll.write(UEEPROM2::from(value)).await?,
+                _ => self.tmp_ll.write(UEEPROM3::from(value)).await?,
+            }
+            write.written += 1;
+        }
 
+        self.wait_eeprom(delay).await?;
+        self.tmp_ll
+            .edit(|r: &mut EEPROM| r.set_unlock(false))
+            .await?;
         Ok(())
     }
 
-    /// Read the data from the eeprom
-    pub async fn read_eeprom(&mut self) -> Result<[u16; 3], Error<E>> {
+    /// Read the user eeprom with the [UserStore] layout, use [UserStore::validate] to check its magic word
+    pub async fn read_user_eeprom_typed(&mut self) -> Result<UserStore, Error<E, PE>> {
+        Ok(self.read_eeprom().await?.into())
+    }
+
+    /// Read back the user eeprom and compare it to the expected values,
+    /// returns [Error::EepromVerifyFailed] with the first word that differs
+    pub async fn verify_eeprom(&mut self, expected: [u16; 3]) -> Result<(), Error<E, PE>> {
+        let got = self.read_eeprom().await?;
+        check_eeprom(expected, got)
+    }
+
+    /// Same as [write_eeprom](Self::write_eeprom), then verify the values with [verify_eeprom](Self::verify_eeprom),
+    /// since programming can fail with a marginal supply. The device is reset before verifying so the words
+    /// are reloaded from the eeprom, which also reloads the configuration, limits and offset stored in it.
+    /// The device is left in shutdown
+    pub async fn write_eeprom_verified<D>(
+        &mut self,
+        values: [u16; 3],
+        delay: &mut D,
+    ) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        self.write_eeprom(values, delay).await?;
+        self.reset(delay).await?;
+        self.wait_eeprom(delay).await?;
+        self.verify_eeprom(values).await
+    }
+
+    /// Read the data from the eeprom. Reading is allowed in any mode, only the writes check at runtime
+    /// that the device is in shutdown since the driver does not track the mode in its type
+    pub async fn read_eeprom(&mut self) -> Result<[u16; 3], Error<E, PE>> {
         let u1: UEEPROM1 = self.tmp_ll.read().await?;
         let u2: UEEPROM2 = self.tmp_ll.read().await?;
         let u3: UEEPROM3 = self.tmp_ll.read().await?;
@@ -313,16 +990,114 @@ where
         Ok([u1.into(), u2.into(), u3.into()])
     }
 
-    /// Wait for data and read the temperature in celsius and goes to shutdown since it's a oneshot
-    pub async fn oneshot(&mut self, average: Average) -> Result<f32, Error<E>> {
-        self.set_oneshot(average).await?;
+    /// Wait for data and read the temperature in celsius. The device goes back to shutdown by itself since it's a oneshot,
+    /// returns [Error::UnexpectedMode] if it did not
+    pub async fn oneshot(&mut self, average: Average) -> Result<f32, Error<E, PE>> {
+        self.oneshot_inner(average, None).await
+    }
+
+    /// Same as [oneshot](Self::oneshot), but also writes the conversion cycle, which affects the oneshot timing
+    pub async fn oneshot_with(
+        &mut self,
+        average: Average,
+        conversion: Conversion,
+    ) -> Result<f32, Error<E, PE>> {
+        self.oneshot_inner(average, Some(conversion)).await
+    }
+
+    async fn oneshot_inner(
+        &mut self,
+        average: Average,
+        conversion: Option<Conversion>,
+    ) -> Result<f32, Error<E, PE>> {
+        self.set_oneshot(average, conversion).await?;
         self.wait_for_data().await?;
 
         let res = self.read_temp_raw().await?;
-        self.set_shutdown().await?;
+        let config: Configuration = self.tmp_ll.read().await?;
+        if config.mode() != ConversionMode::Shutdown {
+            return Err(Error::UnexpectedMode);
+        }
         Ok(res)
     }
 
+    /// Sample with oneshots aligned to each whole second of the clock, which returns milliseconds.
+    /// The conversion is started early by its duration so the data is ready at the boundary,
+    /// `on_sample` is called with the temperature in celsius and the boundary until it returns [ControlFlow::Break]
+    #[cfg(feature = "clock")]
+    pub async fn sample_aligned<C, D, F>(
+        &mut self,
+        clock: &C,
+        delay: &mut D,
+        average: Average,
+        mut on_sample: F,
+    ) -> Result<(), Error<E, PE>>
+    where
+        C: Clock<Instant = u64>,
+        D: DelayNs,
+        F: FnMut(Timestamped<f32, u64>) -> ControlFlow<()>,
+    {
+        let conversion_ms = conversion_period_ms(Conversion::Ms15_5, average) as u64;
+        let mut last = None;
+        loop {
+            let now = clock.now();
+            let mut next = (now / 1000 + 1) * 1000;
+            // A conversion ready slightly early must not sample the same second twice
+            if let Some(last) = last {
+                next = next.max(last + 1000);
+            }
+            let start = next.saturating_sub(conversion_ms);
+            if start > now {
+                delay.delay_ms((start - now) as u32).await;
+            }
+
+            let value = self.oneshot(average).await?;
+            last = Some(next);
+            if on_sample(Timestamped { value, at: next }).is_break() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sample periodically with oneshots, which uses less power than the continuous mode.
+    /// Every `period_ms`, a oneshot is done and `on_sample` is called with the temperature in celsius
+    /// until it returns [ControlFlow::Break]. The delay waits for the remainder of the period after the conversion
+    pub async fn sample_periodic<D, F>(
+        &mut self,
+        delay: &mut D,
+        period_ms: u32,
+        average: Average,
+        mut on_sample: F,
+    ) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+        F: FnMut(f32) -> ControlFlow<()>,
+    {
+        let conversion_ms = conversion_period_ms(Conversion::Ms15_5, average);
+        while let ControlFlow::Continue(()) = on_sample(self.oneshot(average).await?) {
+            delay
+                .delay_ms(period_ms.saturating_sub(conversion_ms))
+                .await;
+        }
+        Ok(())
+    }
+
+    /// Read the temperature of both devices with a oneshot and return the difference in celsius,
+    /// positive if this device is warmer than the other
+    pub async fn read_delta<T2, P2>(
+        &mut self,
+        other: &mut Tmp117<T2, E, P2, PE>,
+        average: Average,
+    ) -> Result<Celsius, Error<E, PE>>
+    where
+        T2: I2c<SevenBitAddress, Error = E>,
+        P2: Wait<Error = PE>,
+    {
+        let temp = self.oneshot(average).await?;
+        let other_temp = other.oneshot(average).await?;
+        Ok(Celsius(temp).delta(Celsius(other_temp)))
+    }
+
     /// Pass a config and closure for the continuous mode.
     /// The device gets set to continuous, then the function is called with the handler
     /// and finally the device is shutdown
@@ -331,15 +1106,49 @@ where
         &mut self,
         config: ContinuousConfig,
         f: F,
-    ) -> Result<(), Error<E>>
+    ) -> Result<(), Error<E, PE>>
     where
-        F: FnOnce(ContinuousHandler<T, E, P>) -> Fut,
-        Fut: Future<Output = Result<(), Error<E>>>,
+        F: FnOnce(ContinuousHandler<T, E, P, PE>) -> Fut,
+        Fut: Future<Output = Result<(), Error<E, PE>>>,
     {
         let continuous = self.set_continuous(config).await?;
         f(continuous).await?;
         self.set_shutdown().await
     }
+
+    /// Use a device already running in continuous mode, for example after [Tmp117::adopt], without reconfiguring it.
+    /// Only the alert pin is switched to data ready. Returns [Error::UnexpectedMode] if the device is not in continuous mode.
+    /// The device is left running after the closure
+    pub async fn resume_continuous<F, Fut>(&mut self, f: F) -> Result<(), Error<E, PE>>
+    where
+        F: FnOnce(ContinuousHandler<T, E, P, PE>) -> Fut,
+        Fut: Future<Output = Result<(), Error<E, PE>>>,
+    {
+        let config: Configuration = self.tmp_ll.read().await?;
+        if config.mode() != ConversionMode::Continuous {
+            return Err(Error::UnexpectedMode);
+        }
+        self.set_data_ready().await?;
+        f(ContinuousHandler { tmp117: self }).await
+    }
+
+    /// Sample in continuous mode, calling `on_sample` with each temperature in celsius
+    /// until it returns [ControlFlow::Break]. The device is then shutdown.
+    /// Each sample waits for the data ready flag, so [Error::DataNotReady] never ends the loop, only bus and alert pin errors do
+    pub async fn run_continuous<F>(
+        &mut self,
+        config: ContinuousConfig,
+        mut on_sample: F,
+    ) -> Result<(), Error<E, PE>>
+    where
+        F: FnMut(f32) -> ControlFlow<()>,
+    {
+        self.continuous(config, |mut t| async move {
+            while let ControlFlow::Continue(()) = on_sample(t.wait_temp().await?) {}
+            Ok(())
+        })
+        .await
+    }
 }
 
 /// Handler for the continuous mode
@@ -347,45 +1156,715 @@ where
 /// # Safety
 /// Note that it is only safe to use in the [Tmp117::continuous] closure since
 /// it uses a pointer to the tmp117 to circuvent issues with async closure lifetime
-pub struct ContinuousHandler<T, E, P> {
-    tmp117: *mut Tmp117<T, E, P>,
+pub struct ContinuousHandler<T, E, P, PE> {
+    tmp117: *mut Tmp117<T, E, P, PE>,
 }
 
-impl<'a, T, E, P> ContinuousHandler<T, E, P>
+impl<T, E, P, PE> ContinuousHandler<T, E, P, PE>
 where
     T: I2c<SevenBitAddress, Error = E>,
     E: embedded_hal::i2c::Error + Copy,
-    P: Wait,
+    P: Wait<Error = PE>,
 {
     /// Read the temperature in celsius, return an error if the value of the temperature is not valid
-    pub async fn read_temp(&mut self) -> Result<f32, Error<E>> {
+    pub async fn read_temp(&mut self) -> Result<f32, Error<E, PE>> {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        if !config.data_ready() {
+            return Err(Error::DataNotReady);
+        }
+
+        tmp117.read_temp_raw().await
+    }
+
+    /// Read the temperature in celsius and the alert flags from the same configuration read used to check the data ready flag,
+    /// saving a transaction compared to [get_alert](Self::get_alert). Return an error if the value of the temperature is not ready
+    pub async fn read_temp_alert_aware(&mut self) -> Result<(f32, Alert), Error<E, PE>> {
         let tmp117 = unsafe { &mut *self.tmp117 };
         let config: Configuration = tmp117.tmp_ll.read().await?;
         if !config.data_ready() {
             return Err(Error::DataNotReady);
         }
 
+        let val = tmp117.read_temp_raw().await?;
+        Ok((val, Alert::from_config(&config)))
+    }
+
+    /// Same as [read_temp_alert_aware](Self::read_temp_alert_aware), but returns a [Measurement] with the raw temperature
+    pub async fn read_measurement(&mut self) -> Result<Measurement, Error<E, PE>> {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        if !config.data_ready() {
+            return Err(Error::DataNotReady);
+        }
+
+        let raw = tmp117.tmp_ll.read_temperature_raw().await?;
+        Ok(Measurement {
+            raw,
+            alert: Alert::from_config(&config),
+        })
+    }
+
+    /// Same as [read_temp](Self::read_temp), but reads again up to `retries` times on [Error::InvalidData],
+    /// since a corrupted read is often transient. Note that the corrupted read may have cleared the data ready flag,
+    /// in which case [Error::DataNotReady] is returned
+    pub async fn read_temp_resilient(&mut self, retries: u8) -> Result<f32, Error<E, PE>> {
+        let mut attempts = 0;
+        loop {
+            match self.read_temp().await {
+                Err(Error::InvalidData) if attempts < retries => attempts += 1,
+                res => return res,
+            }
+        }
+    }
+
+    /// Read the temperature in celsius and timestamp it with the clock,
+    /// return an error if the value of the temperature is not ready
+    #[cfg(feature = "clock")]
+    pub async fn read_temp_at<C>(
+        &mut self,
+        clock: &C,
+    ) -> Result<Timestamped<f32, C::Instant>, Error<E, PE>>
+    where
+        C: Clock,
+    {
+        let value = self.read_temp().await?;
+        Ok(Timestamped {
+            value,
+            at: clock.now(),
+        })
+    }
+
+    /// Returns true if a new temperature is ready. Note that reading the flag clears it,
+    /// use [read_latest](Self::read_latest) to then read the temperature without checking it again
+    pub async fn is_data_ready(&mut self) -> Result<bool, Error<E, PE>> {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        Ok(config.data_ready())
+    }
+
+    /// Read the temperature in celsius without reading the configuration, so the alert flags are not cleared
+    /// and stay available to [get_alert](Self::get_alert). With the alert pin selected as data ready, waits for the pin first,
+    /// otherwise reads the most recent temperature like [read_latest](Self::read_latest)
+    pub async fn read_temp_preserve_alerts(&mut self) -> Result<f32, Error<E, PE>> {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        if let Some(AlertPin::DataReady(_)) = tmp117.alert {
+            tmp117.wait_for_data().await?;
+        }
+        tmp117.read_temp_raw().await
+    }
+
+    /// Read the most recent temperature in celsius without checking the data ready flag.
+    /// Useful for consumers reading less often than the conversion cycle, since the flag may already be cleared.
+    /// Since the configuration is not read, the alert flags are not cleared and stay available to [get_alert](Self::get_alert)
+    pub async fn read_latest(&mut self) -> Result<f32, Error<E, PE>> {
+        let tmp117 = unsafe { &mut *self.tmp117 };
         tmp117.read_temp_raw().await
     }
 
-    /// Wait for the data to be ready and read the temperature in celsius
-    pub async fn wait_temp(&mut self) -> Result<f32, Error<E>> {
+    /// Read the temperature in mK, return an error if the value of the temperature is not ready.
+    /// See [raw_to_millikelvin] for the conversion
+    pub async fn read_temp_millikelvin(&mut self) -> Result<i32, Error<E, PE>> {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        if !config.data_ready() {
+            return Err(Error::DataNotReady);
+        }
+
+        let temp: Temperature = tmp117.tmp_ll.read().await?;
+        Ok(raw_to_millikelvin(temp.into()))
+    }
+
+    /// Trigger a conversion now with a oneshot, wait for it and read the temperature in celsius.
+    /// The delay waits for the nominal conversion time before waiting for the data ready flag.
+    /// The device is then put back in continuous mode, which restarts its conversion cycle
+    pub async fn force_conversion<D>(&mut self, delay: &mut D) -> Result<f32, Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        tmp117
+            .tmp_ll
+            .edit(|r: &mut Configuration| r.set_mode(ConversionMode::OneShot))
+            .await?;
+        delay
+            .delay_ms(conversion_period_ms(Conversion::Ms15_5, config.average()))
+            .await;
+        tmp117.wait_for_data().await?;
+        let temp = tmp117.read_temp_raw().await?;
+        tmp117
+            .tmp_ll
+            .edit(|r: &mut Configuration| r.set_mode(ConversionMode::Continuous))
+            .await?;
+        Ok(temp)
+    }
+
+    /// Wait for and discard `n` readings, to let the averaging settle and skip the first conversion after a mode change.
+    /// The delay waits for the conversion period before waiting for each reading
+    pub async fn warmup<D>(&mut self, n: u8, delay: &mut D) -> Result<(), Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        for _ in 0..n {
+            delay.delay_ms(period).await;
+            self.wait_temp().await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the data to be ready and read the temperature in celsius.
+    /// Once the alert pin is selected as data ready, the configuration is not read anymore
+    /// and each sample is a single i2c transaction, the temperature read. Without a pin, the configuration is polled
+    pub async fn wait_temp(&mut self) -> Result<f32, Error<E, PE>> {
         let tmp117 = unsafe { &mut *self.tmp117 };
         tmp117.set_data_ready().await?;
         tmp117.wait_for_data().await?;
         tmp117.read_temp_raw().await
     }
 
-    /// Check if an alert was triggered since the last calll
-    pub async fn get_alert(&mut self) -> Result<Alert, Error<E>> {
+    /// Same as [read_temp](Self::read_temp), but first waits the interval of the delay if a previous read with it succeeded
+    pub async fn read_temp_delayed<D>(
+        &mut self,
+        delay: &mut ReadDelay<D>,
+    ) -> Result<f32, Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        if delay.pending {
+            delay.delay.delay_us(delay.interval_us).await;
+        }
+        let val = self.read_temp().await?;
+        delay.pending = true;
+        Ok(val)
+    }
+
+    /// Wait for the data to be ready and return the temperature in celsius passed through the filter
+    pub async fn read_temp_filtered(
+        &mut self,
+        filter: &mut IirFilter,
+    ) -> Result<Celsius, Error<E, PE>> {
+        let val = self.wait_temp().await?;
+        Ok(filter.update(Celsius(val)))
+    }
+
+    /// Check if an alert was triggered since the last call. Does a single i2c transaction reading the configuration,
+    /// which latches and clears the alert flags. To handle the alert pin in an interrupt,
+    /// set a flag in the handler and call this from a task since the bus cannot be used in the interrupt
+    pub async fn get_alert(&mut self) -> Result<Alert, Error<E, PE>> {
         let tmp117 = unsafe { &mut *self.tmp117 };
         tmp117.check_alert().await
     }
 
     /// Wait for an alert to come and return it's value
-    pub async fn wait_alert(&mut self) -> Result<Alert, Error<E>> {
+    pub async fn wait_alert(&mut self) -> Result<Alert, Error<E, PE>> {
         let tmp117 = unsafe { &mut *self.tmp117 };
         tmp117.set_alert().await?;
         tmp117.wait_for_alert().await
     }
+
+    /// Wait for the data to be ready and push the temperature in celsius in the queue.
+    /// Returns false if the queue was full and the value was dropped
+    #[cfg(feature = "heapless")]
+    pub async fn read_into_queue<const N: usize>(
+        &mut self,
+        producer: &mut heapless::spsc::Producer<'_, Celsius, N>,
+    ) -> Result<bool, Error<E, PE>> {
+        let val = self.wait_temp().await?;
+        Ok(producer.enqueue(Celsius(val)).is_ok())
+    }
+
+    /// Read the temperature twice, one conversion period apart, and return the second one in celsius.
+    /// Returns [Error::StuckSensor] if both raw values are identical, which is unlikely for a live sensor given the noise on the last bits.
+    /// A very stable environment with a lot of averaging can still give a false positive, retry before considering the sensor dead
+    pub async fn read_temp_liveness<D>(&mut self, delay: &mut D) -> Result<Celsius, Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        tmp117.set_data_ready().await?;
+        tmp117.wait_for_data().await?;
+        let first = tmp117.tmp_ll.read_temperature_raw().await?;
+        delay.delay_ms(period + 1).await;
+        tmp117.wait_for_data().await?;
+        let second = tmp117.tmp_ll.read_temperature_raw().await?;
+        if first == second {
+            return Err(Error::StuckSensor);
+        }
+        Ok(Celsius(decode_temperature(second as u16)))
+    }
+
+    /// Wait for the data and return whether a heating output should be active, for a thermostat without the alert pin.
+    /// Turns on below `setpoint - hysteresis` and off above `setpoint + hysteresis`, otherwise keeps the `prev` state
+    pub async fn control_state(
+        &mut self,
+        setpoint: Celsius,
+        hysteresis: Celsius,
+        prev: bool,
+    ) -> Result<bool, Error<E, PE>> {
+        let temp = self.wait_temp().await?;
+        Ok(heat_on(temp, setpoint, hysteresis, prev))
+    }
+
+    /// Average `n` consecutive temperatures in software on top of the hardware averaging, for a single low noise reading.
+    /// The delay waits for the conversion period before waiting for each one, the mean is computed in m°C with integer math.
+    /// At least one temperature is read
+    pub async fn measure_stable<D>(&mut self, n: u8, delay: &mut D) -> Result<Celsius, Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        let n = n.max(1);
+        let mut sum: i64 = 0;
+        for _ in 0..n {
+            delay.delay_ms(period).await;
+            tmp117.set_data_ready().await?;
+            tmp117.wait_for_data().await?;
+            let raw = tmp117.tmp_ll.read_temperature_raw().await?;
+            sum += raw_to_millicelsius(raw as u16) as i64;
+        }
+        Ok(Celsius((sum / n as i64) as f32 / 1000.0))
+    }
+
+    /// Collect `N` consecutive temperatures, the delay waits for the conversion period before waiting for each one.
+    /// Stops at the first error
+    #[cfg(feature = "heapless")]
+    pub async fn read_burst<D, const N: usize>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<heapless::Vec<Celsius, N>, Error<E, PE>>
+    where
+        D: DelayNs,
+    {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        let config: Configuration = tmp117.tmp_ll.read().await?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        let mut samples = heapless::Vec::new();
+        while !samples.is_full() {
+            delay.delay_ms(period).await;
+            let _ = samples.push(Celsius(self.wait_temp().await?));
+        }
+        Ok(samples)
+    }
+
+    /// Wait for the data to be ready and read the temperature as a uom quantity
+    #[cfg(feature = "uom")]
+    pub async fn read_temp_uom(
+        &mut self,
+    ) -> Result<uom::si::f32::ThermodynamicTemperature, Error<E, PE>> {
+        let val = self.wait_temp().await?;
+        Ok(uom::si::f32::ThermodynamicTemperature::new::<
+            uom::si::thermodynamic_temperature::degree_celsius,
+        >(val))
+    }
+
+    /// Wait for the next event. Depending on the current [AlertPinSelect],
+    /// either waits for the data and returns the temperature or waits for an alert
+    pub async fn wait_event(&mut self) -> Result<Event, Error<E, PE>> {
+        let tmp117 = unsafe { &mut *self.tmp117 };
+        match &tmp117.alert {
+            Some(AlertPin::DataReady(_)) => {
+                tmp117.wait_for_data().await?;
+                Ok(Event::DataReady(Celsius(tmp117.read_temp_raw().await?)))
+            }
+            Some(AlertPin::Alert(_)) => Ok(Event::Alert(tmp117.wait_for_alert().await?)),
+            _ => {
+                // State of the pin is not known, use the register
+                let config: Configuration = tmp117.tmp_ll.read().await?;
+                match config.dr_alert() {
+                    AlertPinSelect::DataReady => {
+                        if !config.data_ready() {
+                            tmp117.wait_for_data().await?;
+                        }
+                        Ok(Event::DataReady(Celsius(tmp117.read_temp_raw().await?)))
+                    }
+                    AlertPinSelect::Alert => match Alert::from_config(&config) {
+                        Alert::None => Ok(Event::Alert(tmp117.wait_for_alert().await?)),
+                        alert => Ok(Event::Alert(alert)),
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl<T, E, P, PE> SensorRead for ContinuousHandler<T, E, P, PE>
+where
+    T: I2c<SevenBitAddress, Error = E>,
+    E: embedded_hal::i2c::Error + Copy,
+    P: Wait<Error = PE>,
+{
+    type Value = Celsius;
+    type Error = Error<E, PE>;
+
+    async fn read(&mut self) -> Result<Celsius, Error<E, PE>> {
+        self.wait_temp().await.map(Celsius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{pin::pin, task::Poll};
+
+    use crate::test_util::{block_on, poll_once, MockDelay, MockPin, RecordingI2c};
+
+    type Bus = RecordingI2c<256>;
+
+    const HOT: u16 = 0x6400;
+
+    #[test]
+    fn wait_event_data_ready_pin() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        let mut event = None;
+        let out = &mut event;
+        block_on(tmp.continuous(Default::default(), |mut t| async move {
+            *out = Some(t.wait_event().await?);
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(event, Some(Event::DataReady(Celsius(25.0))));
+        assert_eq!(pin.low_waits, 1);
+    }
+
+    #[test]
+    fn wait_temp_polled_manually() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        let mut temp = None;
+        let out = &mut temp;
+        let mut polls = 0;
+        {
+            let mut fut = pin!(tmp.continuous(Default::default(), |mut t| async move {
+                *out = Some(t.wait_temp().await?);
+                Ok(())
+            }));
+            // The mocks never return pending, an executor would sleep between the polls otherwise
+            let res = loop {
+                polls += 1;
+                if let Poll::Ready(res) = poll_once(fut.as_mut()) {
+                    break res;
+                }
+            };
+            assert_eq!(res, Ok(()));
+        }
+        assert_eq!(polls, 1);
+        assert_eq!(temp, Some(25.0));
+        assert_eq!(pin.low_waits, 1);
+    }
+
+    #[test]
+    fn wait_event_alert_pin() {
+        let mut bus = Bus::new();
+        bus.temperature = HOT;
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        let config = ContinuousConfig {
+            pin_select: Some(AlertPinSelect::Alert),
+            ..Default::default()
+        };
+        let mut event = None;
+        let out = &mut event;
+        block_on(tmp.continuous(config, |mut t| async move {
+            *out = Some(t.wait_event().await?);
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(event, Some(Event::Alert(Alert::High)));
+        assert_eq!(pin.low_waits, 1);
+    }
+
+    #[test]
+    fn pin_kept_as_data_ready_transactions() {
+        let mut bus = Bus::new();
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        block_on(tmp.use_pin_as_data_ready(Polarity::ActiveLow)).unwrap();
+        block_on(tmp.continuous(Default::default(), |mut t| async move {
+            for _ in 0..10 {
+                t.wait_temp().await?;
+            }
+            Ok(())
+        }))
+        .unwrap();
+
+        // The configuration is only read by the edits selecting the pin, entering and leaving continuous
+        assert_eq!(bus.reads(0x01), 3);
+        assert_eq!(bus.reads(0x00), 10);
+        assert_eq!(pin.low_waits, 10);
+    }
+
+    #[test]
+    fn pin_kept_as_data_ready_without_pin() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(
+            block_on(tmp.use_pin_as_data_ready(Polarity::ActiveLow)),
+            Err(Error::NoAlertPin)
+        );
+        assert!(bus.transactions().is_empty());
+    }
+
+    #[test]
+    fn eeprom_write_resumes_after_drop() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0400;
+        // Each word programmed keeps the eeprom busy
+        bus.program_polls = 1;
+        let mut delay = MockDelay {
+            yields: true,
+            ..Default::default()
+        };
+        let mut write = EepromWrite::new([0x1111, 0x2222, 0x3333]);
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        {
+            // Pending on the busy poll after the first word, then dropped
+            let fut = pin!(tmp.resume_eeprom_write(&mut write, &mut delay));
+            assert!(poll_once(fut).is_pending());
+        }
+        assert_eq!(write.written(), 1);
+        block_on(tmp.resume_eeprom_write(&mut write, &mut delay)).unwrap();
+        assert!(write.is_done());
+        assert_eq!(bus.eeprom[5..8], [0x1111, 0x2222, 0x3333]);
+        assert_eq!(bus.writes(0x05), 1);
+        assert_eq!(bus.registers[4] & 0x8000, 0);
+    }
+
+    #[test]
+    fn alert_pin_select_updates_bit_and_state() {
+        let mut bus = Bus::new();
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        block_on(tmp.set_alert_pin_select(AlertPinSelect::DataReady)).unwrap();
+        assert_eq!(
+            block_on(tmp.get_alert_pin_select()),
+            Ok(AlertPinSelect::DataReady)
+        );
+        assert!(matches!(tmp.alert, Some(AlertPin::DataReady(_))));
+
+        // The pin is already in data ready, waiting for data does not select it again
+        block_on(tmp.continuous(Default::default(), |mut t| async move {
+            t.wait_temp().await?;
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(bus.writes(0x01), 3);
+        assert_eq!(bus.registers[1] & 0x0004, 0x0004);
+    }
+
+    #[test]
+    fn eeprom_write_rejected_in_continuous() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(
+            block_on(tmp.write_eeprom([1, 2, 3], &mut MockDelay::new())),
+            Err(Error::BusyConverting)
+        );
+        assert!((5..=7).all(|r| bus.writes(r) == 0));
+    }
+
+    #[test]
+    fn pin_error_propagates() {
+        let mut bus = Bus::new();
+        let mut pin = MockPin::new();
+        pin.fail_next(embedded_hal::digital::ErrorKind::Other);
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        assert_eq!(
+            block_on(tmp.oneshot(Average::NoAverage)),
+            Err(Error::AlertPin(embedded_hal::digital::ErrorKind::Other))
+        );
+    }
+
+    async fn read_pair<S: SensorRead>(sensor: &mut S) -> Result<[S::Value; 2], S::Error> {
+        Ok([sensor.read().await?, sensor.read().await?])
+    }
+
+    #[test]
+    fn sensor_read_generic_consumer() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let mut values = None;
+        let out = &mut values;
+        block_on(tmp.continuous(Default::default(), |mut t| async move {
+            *out = Some(read_pair(&mut t).await?);
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(values, Some([Celsius(25.0); 2]));
+        assert_eq!(bus.reads(0x00), 2);
+    }
+
+    #[test]
+    fn data_ready_pin_reloaded_after_reset() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0400;
+        // Unlocked so the selection is saved to the eeprom
+        bus.registers[4] = 0x8000;
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        tmp.allow_eeprom_programming(true);
+        block_on(tmp.set_alert_pin_select(AlertPinSelect::DataReady)).unwrap();
+        assert_eq!(bus.eeprom[1] & 0x0004, 0x0004);
+
+        bus.registers[4] = 0;
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        assert!(matches!(tmp.alert, Some(AlertPin::Unkown(_))));
+        block_on(tmp.reset(&mut delay)).unwrap();
+        assert!(matches!(tmp.alert, Some(AlertPin::DataReady(_))));
+        assert_eq!(bus.registers[1] & 0x0004, 0x0004);
+    }
+
+    #[test]
+    fn thermal_alert_without_temperature_reads() {
+        let mut bus = Bus::new();
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        block_on(tmp.arm_thermal_alert(Celsius(30.0), Celsius(2.0), Polarity::ActiveHigh)).unwrap();
+        block_on(tmp.wait_thermal_alert()).unwrap();
+        assert_eq!(pin.high_waits, 1);
+        assert_eq!(bus.registers[2..4], [0x0F00, 0x0E00]);
+        assert_eq!(bus.registers[1] & 0x0C1C, 0x0018);
+        assert_eq!(bus.reads(0x00), 0);
+
+        // Without a pin, the high alert flag is polled
+        let mut bus = Bus::new();
+        bus.temperature = HOT;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        block_on(tmp.arm_thermal_alert(Celsius(30.0), Celsius(2.0), Polarity::ActiveLow)).unwrap();
+        block_on(tmp.wait_thermal_alert()).unwrap();
+        assert_eq!(bus.reads(0x00), 0);
+    }
+
+    #[test]
+    fn configured_polarity_used_for_pin() {
+        let mut bus = Bus::new();
+        bus.temperature = HOT;
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        let circuit = AlertPinConfig {
+            active_high: true,
+            pull_up: true,
+        };
+        block_on(tmp.set_alert_pin_config(&circuit)).unwrap();
+        block_on(tmp.continuous(Default::default(), |mut t| async move {
+            t.wait_temp().await?;
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(bus.registers[1] & 0x000C, 0x000C);
+
+        let config = ContinuousConfig {
+            pin_select: Some(AlertPinSelect::Alert),
+            ..Default::default()
+        };
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        block_on(tmp.set_alert_pin_config(&circuit)).unwrap();
+        block_on(tmp.continuous(config, |mut t| async move {
+            assert_eq!(t.wait_alert().await?, Alert::High);
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(bus.registers[1] & 0x000C, 0x0008);
+        assert_eq!(pin.high_waits, 2);
+        assert_eq!(pin.low_waits, 0);
+    }
+
+    #[test]
+    fn thermal_alert_pin_error_recovers() {
+        let mut bus = Bus::new();
+        let mut pin = MockPin::new();
+        pin.fail_next(embedded_hal::digital::ErrorKind::Other);
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        block_on(tmp.arm_thermal_alert(Celsius(30.0), Celsius(2.0), Polarity::ActiveLow)).unwrap();
+        assert!(matches!(tmp.alert, Some(AlertPin::Alert(_))));
+        assert_eq!(
+            block_on(tmp.wait_thermal_alert()),
+            Err(Error::AlertPin(embedded_hal::digital::ErrorKind::Other))
+        );
+        assert!(matches!(tmp.alert, Some(AlertPin::Unkown(_))));
+        assert_eq!(block_on(tmp.wait_thermal_alert()), Ok(()));
+        assert_eq!(pin.low_waits, 2);
+    }
+
+    #[test]
+    fn read_temp_preserve_alerts_skips_configuration() {
+        let mut bus = Bus::new();
+        bus.temperature = HOT;
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        block_on(tmp.use_pin_as_data_ready(Polarity::ActiveLow)).unwrap();
+        block_on(tmp.continuous(Default::default(), |mut t| async move {
+            for _ in 0..3 {
+                t.read_temp_preserve_alerts().await?;
+            }
+            t.read_latest().await?;
+            Ok(())
+        }))
+        .unwrap();
+
+        // Reading the configuration clears the alert flags, it is only read to select the pin, enter and leave continuous
+        assert_eq!(bus.reads(0x01), 3);
+        assert_eq!(bus.reads(0x00), 4);
+        assert_eq!(pin.low_waits, 3);
+    }
+
+    #[test]
+    fn pin_config_set_on_entering_continuous() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        // Left by someone else in alert mode, active high
+        bus.registers[1] = 0x0228;
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        let config = ContinuousConfig {
+            pin_select: Some(AlertPinSelect::DataReady),
+            ..Default::default()
+        };
+        let mut temp = 0.0;
+        let out = &mut temp;
+        block_on(tmp.continuous(config, |mut t| async move {
+            *out = t.wait_temp().await?;
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(temp, 25.0);
+        assert!(matches!(tmp.alert, Some(AlertPin::DataReady(_))));
+        assert_eq!(bus.registers[1] & 0x000C, 0x0004);
+        assert_eq!(pin.low_waits, 1);
+        assert_eq!(pin.high_waits, 0);
+    }
+
+    #[test]
+    fn cached_data_ready_pin_one_transaction_per_sample() {
+        let mut bus = Bus::new();
+        let mut pin = MockPin::new();
+        let mut tmp = Tmp117::new_alert(&mut bus, 0x48, &mut pin);
+        block_on(tmp.continuous(Default::default(), |mut t| async move {
+            for _ in 0..10 {
+                t.wait_temp().await?;
+            }
+            Ok(())
+        }))
+        .unwrap();
+        assert!(tmp.data_ready_pin.is_none());
+
+        // The configuration is only read by the edits selecting the pin, entering and leaving continuous
+        assert_eq!(bus.reads(0x01), 3);
+        assert_eq!(bus.reads(0x00), 10);
+        assert_eq!(pin.low_waits, 10);
+    }
 }
@@ -7,12 +7,23 @@ use embedded_hal::i2c::SevenBitAddress;
 use embedded_hal_async::i2c::I2c;
 
 use crate::error::ErrorLL;
-use crate::register::Address;
+use crate::register::{Address, Temperature};
+use crate::tmp117_ll::{encode_write, TransactionHook};
 
 /// Async low level driver of the TPM117. Allows to read, write and edit the registers directly via the i2c bus
-pub struct Tmp117LL<T, E> {
+///
+/// Use the [ReadRegister](device_register_async::ReadRegister), [WriteRegister](device_register_async::WriteRegister)
+/// and [EditRegister](device_register_async::EditRegister) traits to access the registers, they only accept registers with the right permission.
+/// Writing a read only register such as [Temperature] is a compile error.
+///
+/// A [TransactionHook] can be given with [Tmp117LL::with_hook] to run code around each i2c access.
+///
+/// The bus can be borrowed instead of owned, `&mut T` implements [I2c] for any bus `T`,
+/// which allows sharing it with other drivers between uses.
+pub struct Tmp117LL<T, E, H = ()> {
     i2c: T,
     addr: u8,
+    hook: H,
     e: PhantomData<E>,
 }
 
@@ -23,40 +34,96 @@ where
 {
     /// Creates a new instace of the Tmp117 from an i2c bus
     pub fn new(i2c: T, addr: u8) -> Self {
+        Self::with_hook(i2c, addr, ())
+    }
+}
+
+impl<'a, T, E> Tmp117LL<&'a mut T, E>
+where
+    T: I2c<SevenBitAddress, Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Creates a new instance borrowing the i2c bus, for a short lived driver doing a one-off access.
+    /// The bus is usable again once the driver is dropped
+    pub fn with_borrowed(i2c: &'a mut T, addr: u8) -> Self {
+        Self::new(i2c, addr)
+    }
+}
+
+impl<T, E, H> Tmp117LL<T, E, H>
+where
+    T: I2c<SevenBitAddress, Error = E>,
+    E: embedded_hal::i2c::Error,
+    H: TransactionHook,
+{
+    /// Creates a new instance of the Tmp117 from an i2c bus, calling the hook around each i2c access
+    pub fn with_hook(i2c: T, addr: u8, hook: H) -> Self {
         Self {
             i2c,
             addr,
+            hook,
             e: PhantomData,
         }
     }
+
+    /// Change the i2c address of the device accessed, to reuse the driver for another device behind a multiplexer for example.
+    /// The driver does not switch the multiplexer channel and keeps no state per device, switch it before the next access
+    pub fn set_address(&mut self, addr: u8) {
+        self.addr = addr;
+    }
+
+    /// Returns the hook, to inspect or change its state
+    pub fn hook_mut(&mut self) -> &mut H {
+        &mut self.hook
+    }
+
+    async fn write_read(&mut self, address: Address, buff: &mut [u8; 2]) -> Result<(), ErrorLL<E>> {
+        self.hook.pre_transaction();
+        let res = self.i2c.write_read(self.addr, &[address.0], buff).await;
+        self.hook.post_transaction();
+        res.map_err(ErrorLL::Bus)
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), ErrorLL<E>> {
+        self.hook.pre_transaction();
+        let res = self.i2c.write(self.addr, bytes).await;
+        self.hook.post_transaction();
+        res.map_err(ErrorLL::Bus)
+    }
+
+    /// Reads the raw value of the register at the address, without decoding it
+    pub async fn read_raw(&mut self, address: Address) -> Result<u16, ErrorLL<E>> {
+        let mut buff = [0; 2];
+        self.write_read(address, &mut buff).await?;
+        Ok(u16::from_be_bytes(buff))
+    }
+
+    /// Reads the temperature register as a sign extended value, one lsb is [CELCIUS_CONVERSION](crate::CELCIUS_CONVERSION).
+    /// Does a single transaction without going through the generic register decoding, for the hot path
+    pub async fn read_temperature_raw(&mut self) -> Result<i16, ErrorLL<E>> {
+        let val = self.read_raw(Temperature::ADDRESS).await?;
+        Ok(val as i16)
+    }
 }
 
-impl<T, E, R> RegisterInterface<R, Address> for Tmp117LL<T, E>
+impl<T, E, H, R> RegisterInterface<R, Address> for Tmp117LL<T, E, H>
 where
     R: Register<Address = Address> + Clone + TryFrom<u16>,
     u16: From<R>,
     T: I2c<SevenBitAddress, Error = E>,
     E: embedded_hal::i2c::Error,
+    H: TransactionHook,
 {
     type Error = ErrorLL<E>;
 
     async fn read_register(&mut self) -> Result<R, Self::Error> {
         let mut buff = [0; 2];
-        self.i2c
-            .write_read(self.addr, &[R::ADDRESS.0], &mut buff)
-            .await
-            .map_err(ErrorLL::Bus)?;
-        let val = u16::from_be_bytes(buff[0..2].try_into().unwrap());
+        self.write_read(R::ADDRESS, &mut buff).await?;
+        let val = u16::from_be_bytes(buff);
         R::try_from(val).map_err(|_| ErrorLL::InvalidData)
     }
 
     async fn write_register(&mut self, register: &R) -> Result<(), Self::Error> {
-        let val: u16 = register.clone().into();
-        let packet = val.to_be_bytes();
-
-        self.i2c
-            .write(self.addr, &[R::ADDRESS.0, packet[0], packet[1]])
-            .await
-            .map_err(ErrorLL::Bus)
+        self.write(&encode_write(register)).await
     }
 }
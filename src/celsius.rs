@@ -0,0 +1,289 @@
+//! Temperature in celsius helpers
+
+use core::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Div, Mul, Sub},
+    str::FromStr,
+};
+
+use crate::{decode_temperature, register::celsius_to_raw, CELCIUS_CONVERSION};
+
+/// A temperature in celsius, as returned by the drivers.
+/// Formatted with two decimals with defmt, which is enough for the resolution of the device
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct Celsius(pub f32);
+
+impl Celsius {
+    /// Restrict the temperature to the range, for a display for example.
+    /// Unlike [f32::clamp] this never panics: swapped bounds are reordered and a NaN bound is ignored
+    pub fn clamp(self, min: Celsius, max: Celsius) -> Celsius {
+        let (min, max) = if min.0 > max.0 {
+            (max, min)
+        } else {
+            (min, max)
+        };
+        if self.0 < min.0 {
+            min
+        } else if self.0 > max.0 {
+            max
+        } else {
+            self
+        }
+    }
+
+    /// Returns the temperature the device stores for this value once written to a limit or offset,
+    /// quantized to one lsb of [CELCIUS_CONVERSION]
+    pub fn quantize(self) -> Celsius {
+        Celsius(decode_temperature(celsius_to_raw(self.0)))
+    }
+
+    /// One lsb of the device, the resolution of the readings
+    pub const LSB: Celsius = Celsius(CELCIUS_CONVERSION);
+
+    /// Returns true if both temperatures differ by at most `epsilon`, bound included.
+    /// Use [Celsius::LSB] to compare readings at the resolution of the device
+    pub fn approx_eq(self, other: Celsius, epsilon: Celsius) -> bool {
+        let diff = self.0 - other.0;
+        -epsilon.0 <= diff && diff <= epsilon.0
+    }
+
+    /// Returns the difference with the other temperature, positive if this one is warmer
+    pub fn delta(self, other: Celsius) -> Celsius {
+        self - other
+    }
+
+    /// Returns true if the temperature is in the range, bounds included
+    pub fn is_within(self, min: Celsius, max: Celsius) -> bool {
+        min <= self && self <= max
+    }
+
+    /// Returns the sign, the integer part and the hundredths, rounded to the nearest hundredth.
+    /// Goes through integer millicelsius so formatting does not need float formatting
+    #[cfg(any(feature = "defmt", test))]
+    fn centi(self) -> (bool, u32, u32) {
+        let half = if self.0 < 0.0 { -0.5 } else { 0.5 };
+        let milli = (self.0 * 1000.0 + half) as i32;
+        let centi = (milli.unsigned_abs() + 5) / 10;
+        (milli < 0 && centi != 0, centi / 100, centi % 100)
+    }
+}
+
+// The arithmetic is done in f32. Readings are multiples of one lsb on 16 bits,
+// so sums of up to 512 readings are exact and only the divisions round
+
+impl Add for Celsius {
+    type Output = Celsius;
+
+    fn add(self, rhs: Celsius) -> Celsius {
+        Celsius(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Celsius {
+    type Output = Celsius;
+
+    fn sub(self, rhs: Celsius) -> Celsius {
+        Celsius(self.0 - rhs.0)
+    }
+}
+
+impl Mul<f32> for Celsius {
+    type Output = Celsius;
+
+    fn mul(self, rhs: f32) -> Celsius {
+        Celsius(self.0 * rhs)
+    }
+}
+
+impl Div<f32> for Celsius {
+    type Output = Celsius;
+
+    fn div(self, rhs: f32) -> Celsius {
+        Celsius(self.0 / rhs)
+    }
+}
+
+impl Sum for Celsius {
+    fn sum<I: Iterator<Item = Celsius>>(iter: I) -> Celsius {
+        iter.fold(Celsius(0.0), Add::add)
+    }
+}
+
+impl From<f32> for Celsius {
+    fn from(value: f32) -> Self {
+        Celsius(value)
+    }
+}
+
+impl From<Celsius> for f32 {
+    fn from(value: Celsius) -> Self {
+        value.0
+    }
+}
+
+/// Displayed as `23.45°C`, the precision of the formatter is used if given
+impl fmt::Display for Celsius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*}°C", precision, self.0),
+            None => write!(f, "{}°C", self.0),
+        }
+    }
+}
+
+/// Error returned when parsing a [Celsius] from a string
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ParseCelsiusError {
+    /// The string is not a number
+    Invalid,
+
+    /// The temperature is outside of the ±256°C range of the device
+    OutOfRange,
+}
+
+impl fmt::Display for ParseCelsiusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseCelsiusError::Invalid => f.write_str("invalid temperature"),
+            ParseCelsiusError::OutOfRange => f.write_str("temperature out of range"),
+        }
+    }
+}
+
+/// Parses `23.45`, `23.45C` or `23.45°C`, surrounding whitespace is ignored
+impl FromStr for Celsius {
+    type Err = ParseCelsiusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_suffix('C').unwrap_or(s);
+        let s = s.strip_suffix('°').unwrap_or(s).trim_end();
+        let value: f32 = s.parse().map_err(|_| ParseCelsiusError::Invalid)?;
+        if value.is_nan() {
+            return Err(ParseCelsiusError::Invalid);
+        }
+        if !(-256.0..=256.0).contains(&value) {
+            return Err(ParseCelsiusError::OutOfRange);
+        }
+        Ok(Celsius(value))
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Celsius {
+    fn format(&self, f: defmt::Formatter) {
+        let (negative, int, frac) = self.centi();
+        let sign = if negative { "-" } else { "" };
+        defmt::write!(f, "{=str}{}.{}{}", sign, int, frac / 10, frac % 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_bounds() {
+        let (min, max) = (Celsius(-20.0), Celsius(50.0));
+        assert_eq!(Celsius(-20.0).clamp(min, max), min);
+        assert_eq!(Celsius(50.0).clamp(min, max), max);
+        assert_eq!(Celsius(-40.0).clamp(min, max), min);
+        assert_eq!(Celsius(120.0).clamp(min, max), max);
+        assert_eq!(Celsius(21.5).clamp(min, max), Celsius(21.5));
+    }
+
+    #[test]
+    fn clamp_swapped_or_nan_bounds() {
+        let (min, max) = (Celsius(-20.0), Celsius(50.0));
+        assert_eq!(Celsius(-40.0).clamp(max, min), min);
+        assert_eq!(Celsius(120.0).clamp(max, min), max);
+        assert_eq!(Celsius(21.5).clamp(max, min), Celsius(21.5));
+
+        let nan = Celsius(f32::NAN);
+        assert_eq!(Celsius(-40.0).clamp(nan, max), Celsius(-40.0));
+        assert_eq!(Celsius(120.0).clamp(nan, max), max);
+        assert_eq!(Celsius(-40.0).clamp(min, nan), min);
+        assert_eq!(Celsius(120.0).clamp(nan, nan), Celsius(120.0));
+    }
+
+    #[test]
+    fn centi_rounding() {
+        assert_eq!(Celsius(23.45).centi(), (false, 23, 45));
+        assert_eq!(Celsius(-0.004).centi(), (false, 0, 0));
+        assert_eq!(Celsius(-12.345).centi(), (true, 12, 35));
+        assert_eq!(Celsius(0.0).centi(), (false, 0, 0));
+        assert_eq!(Celsius(-0.005).centi(), (true, 0, 1));
+        assert_eq!(Celsius(25.0 + CELCIUS_CONVERSION).centi(), (false, 25, 1));
+    }
+
+    #[test]
+    fn parse_strings() {
+        assert_eq!("23.45".parse(), Ok(Celsius(23.45)));
+        assert_eq!("23.45C".parse(), Ok(Celsius(23.45)));
+        assert_eq!(" 23.45 °C ".parse(), Ok(Celsius(23.45)));
+        assert_eq!("-40".parse(), Ok(Celsius(-40.0)));
+        assert_eq!("256".parse(), Ok(Celsius(256.0)));
+
+        assert_eq!("".parse::<Celsius>(), Err(ParseCelsiusError::Invalid));
+        assert_eq!("warm".parse::<Celsius>(), Err(ParseCelsiusError::Invalid));
+        assert_eq!("NaN".parse::<Celsius>(), Err(ParseCelsiusError::Invalid));
+        assert_eq!("23.45F".parse::<Celsius>(), Err(ParseCelsiusError::Invalid));
+        assert_eq!("300".parse::<Celsius>(), Err(ParseCelsiusError::OutOfRange));
+        assert_eq!(
+            "-inf".parse::<Celsius>(),
+            Err(ParseCelsiusError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn quantize_rounds_to_nearest() {
+        assert_eq!(
+            Celsius(25.0077).quantize(),
+            Celsius(25.0 + CELCIUS_CONVERSION)
+        );
+        assert_eq!(Celsius(25.003).quantize(), Celsius(25.0));
+        assert_eq!(
+            Celsius(-25.0077).quantize(),
+            Celsius(-25.0 - CELCIUS_CONVERSION)
+        );
+        assert_eq!(Celsius(-25.003).quantize(), Celsius(-25.0));
+        assert_eq!(
+            Celsius(300.0).quantize(),
+            Celsius(256.0 - CELCIUS_CONVERSION)
+        );
+        assert_eq!(Celsius(-300.0).quantize(), Celsius(-256.0));
+    }
+
+    #[test]
+    fn approx_eq_one_lsb() {
+        let base = Celsius(25.0);
+        let one_lsb = Celsius(25.0 + CELCIUS_CONVERSION);
+        assert!(base.approx_eq(one_lsb, Celsius::LSB));
+        assert!(one_lsb.approx_eq(base, Celsius::LSB));
+        let beyond = Celsius(25.0 + CELCIUS_CONVERSION + 0.001);
+        assert!(!base.approx_eq(beyond, Celsius::LSB));
+        assert!(!beyond.approx_eq(base, Celsius::LSB));
+        assert!(base.approx_eq(base, Celsius(0.0)));
+    }
+
+    #[test]
+    fn arithmetic_and_sum() {
+        let (a, b) = (Celsius(20.5), Celsius(22.0));
+        assert_eq!(a + b, Celsius(42.5));
+        assert_eq!(a - b, Celsius(-1.5));
+        assert_eq!(a * 2.0, Celsius(41.0));
+        assert_eq!(b / 4.0, Celsius(5.5));
+        assert_eq!((a + b) / 2.0, Celsius(21.25));
+        assert_eq!(
+            [a, b, Celsius(-2.5)].into_iter().sum::<Celsius>(),
+            Celsius(40.0)
+        );
+        assert_eq!(
+            core::iter::empty::<Celsius>().sum::<Celsius>(),
+            Celsius(0.0)
+        );
+    }
+}
@@ -1,20 +1,65 @@
 //! Errors used for the driver
 
-/// Error emitted by the TMP117 drivers
+use core::convert::Infallible;
+
+use crate::Id;
+
+/// Error emitted by the TMP117 drivers. `PE` is the error of the alert pin, if any
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum Error<E> {
+pub enum Error<E, PE = Infallible> {
     /// Internal i2c bus error
     Bus(E),
 
     /// Data is not ready
     DataNotReady,
 
-    /// Alert pin error
-    AlertPin,
+    /// Alert pin error. The driver forgets the state of the pin,
+    /// so retrying the operation configures the pin function again
+    AlertPin(PE),
 
     /// Received Invalid data
     InvalidData,
+
+    /// The device is not in the expected conversion mode
+    UnexpectedMode,
+
+    /// The device ID does not match the one of the TMP117
+    UnexpectedDevice(Id),
+
+    /// The conversion cycle is shorter than the time needed to service the data
+    SampleRateTooHigh,
+
+    /// The device is converting, it must be in shutdown for this operation
+    BusyConverting,
+
+    /// The eeprom is unlocked, the write would also program the eeprom
+    EepromUnlocked,
+
+    /// The temperature is outside of the ±256 °C range of the registers
+    LimitOutOfRange,
+
+    /// The eeprom stayed busy longer than a write should take
+    EepromTimeout,
+
+    /// Two conversions returned the exact same raw temperature, the sensor may be stuck
+    StuckSensor,
+
+    /// A word of the user eeprom read back differs from the one written
+    EepromVerifyFailed {
+        /// The index of the word
+        index: usize,
+        /// The value written
+        expected: u16,
+        /// The value read back
+        got: u16,
+    },
+
+    /// The operation needs the alert pin, but the driver was created without one
+    NoAlertPin,
+
+    /// The circuit of the alert pin cannot reach the active level, see [AlertPinConfig](crate::AlertPinConfig)
+    UnreachablePolarity,
 }
 
 /// Error emitted by the low level TMP117 drivers
@@ -28,7 +73,7 @@ pub enum ErrorLL<E> {
     InvalidData,
 }
 
-impl<E> From<ErrorLL<E>> for Error<E> {
+impl<E, PE> From<ErrorLL<E>> for Error<E, PE> {
     fn from(value: ErrorLL<E>) -> Self {
         match value {
             ErrorLL::Bus(e) => Error::Bus(e),
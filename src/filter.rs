@@ -0,0 +1,73 @@
+//! Software filters applied over the readings
+
+use crate::Celsius;
+
+/// First order low pass filter (IIR), smooths the readings without increasing the hardware averaging latency.
+///
+/// `alpha` is between 0 and 1, the weight of each new sample. For a sampling period `dt` and a cutoff frequency `fc`,
+/// `alpha = dt / (dt + 1 / (2 * pi * fc))`. A smaller alpha filters more but reacts slower.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct IirFilter {
+    alpha: f32,
+    /// NaN until the first sample
+    state: Celsius,
+}
+
+impl IirFilter {
+    /// Create a new filter, alpha is clamped between 0 and 1
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            state: Celsius(f32::NAN),
+        }
+    }
+
+    /// Add a sample and return the filtered value. The first sample initializes the filter
+    pub fn update(&mut self, sample: Celsius) -> Celsius {
+        self.state = if self.state.0.is_nan() {
+            sample
+        } else {
+            self.state + (sample - self.state) * self.alpha
+        };
+        self.state
+    }
+
+    /// Returns the current filtered value, `None` if no sample was added
+    pub fn value(&self) -> Option<Celsius> {
+        if self.state.0.is_nan() {
+            None
+        } else {
+            Some(self.state)
+        }
+    }
+
+    /// Reset the filter, the next sample initializes it again
+    pub fn reset(&mut self) {
+        self.state = Celsius(f32::NAN);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_response_converges() {
+        let mut filter = IirFilter::new(0.5);
+        assert_eq!(filter.value(), None);
+        assert_eq!(filter.update(Celsius(20.0)), Celsius(20.0));
+        let mut prev = Celsius(20.0);
+        for _ in 0..20 {
+            let val = filter.update(Celsius(30.0));
+            assert!(val > prev && val <= Celsius(30.0));
+            prev = val;
+        }
+        assert!(prev.approx_eq(Celsius(30.0), Celsius(0.001)));
+        assert_eq!(filter.value(), Some(prev));
+
+        filter.reset();
+        assert_eq!(filter.value(), None);
+        assert_eq!(filter.update(Celsius(10.0)), Celsius(10.0));
+    }
+}
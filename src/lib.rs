@@ -2,24 +2,81 @@
 #![no_std]
 #![deny(missing_docs)]
 
-use device_register::{EditRegister, ReadRegister, WriteRegister};
+use core::{
+    fmt,
+    ops::ControlFlow,
+    task::{Context, Poll},
+};
+
+pub use celsius::Celsius;
+use device_register::{EditRegister, ReadRegister, Register, RegisterInterface, WriteRegister};
 use embedded_hal::{
     delay::DelayNs,
-    i2c::{I2c, SevenBitAddress},
+    digital::PinState,
+    i2c::{ErrorKind, I2c, NoAcknowledgeSource, SevenBitAddress},
 };
 pub use error::Error;
+use filter::IirFilter;
 use register::*;
 use tmp117_ll::Tmp117LL;
 
 pub mod asynchronous;
+pub mod celsius;
 pub mod error;
+pub mod filter;
 pub mod register;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod tmp117_ll;
 
 /// Conversion factor used by the device. One lsb is this value
 pub const CELCIUS_CONVERSION: f32 = 0.0078125;
 
+/// Decodes the raw value of the temperature register to celsius, as done by the drivers
+pub fn decode_temperature(raw: u16) -> f32 {
+    // Convert to i16 for two complements
+    (raw as i16) as f32 * CELCIUS_CONVERSION
+}
+
+/// The connection of the ADD0 pin, which selects the i2c address of the device
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Strap {
+    /// ADD0 connected to ground
+    Gnd,
+
+    /// ADD0 connected to V+
+    Vcc,
+
+    /// ADD0 connected to SDA
+    Sda,
+
+    /// ADD0 connected to SCL
+    Scl,
+}
+
+impl Strap {
+    /// Returns the i2c address selected by the strap
+    pub const fn address(self) -> u8 {
+        match self {
+            Strap::Gnd => 0x48,
+            Strap::Vcc => 0x49,
+            Strap::Sda => 0x4A,
+            Strap::Scl => 0x4B,
+        }
+    }
+}
+
+// Addresses from the datasheet, checked at compile time
+const _: () = assert!(Strap::Gnd.address() == 0x48);
+const _: () = assert!(Strap::Vcc.address() == 0x49);
+const _: () = assert!(Strap::Sda.address() == 0x4A);
+const _: () = assert!(Strap::Scl.address() == 0x4B);
+
 /// The types of alerts possible
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Alert {
     /// No alert were triggered
     None,
@@ -34,8 +91,155 @@ pub enum Alert {
     HighLow,
 }
 
-/// The continuous config
-#[derive(Default)]
+impl Alert {
+    /// Returns the logic level the alert pin would have for this alert.
+    /// Any alert drives the pin to its active level, which depends on the [Polarity]
+    pub fn as_pin_state(&self, polarity: Polarity) -> PinState {
+        let active = !matches!(self, Alert::None);
+        match polarity {
+            Polarity::ActiveHigh => PinState::from(active),
+            Polarity::ActiveLow => PinState::from(!active),
+        }
+    }
+
+    fn from_config(config: &Configuration) -> Self {
+        if config.high_alert() && config.low_alert() {
+            Alert::HighLow
+        } else if config.high_alert() {
+            Alert::High
+        } else if config.low_alert() {
+            Alert::Low
+        } else {
+            Alert::None
+        }
+    }
+}
+
+/// Packs the alert in a bitmask, bit 0 is the low alert and bit 1 the high alert
+impl From<Alert> for u8 {
+    fn from(alert: Alert) -> Self {
+        match alert {
+            Alert::None => 0,
+            Alert::Low => 1,
+            Alert::High => 2,
+            Alert::HighLow => 3,
+        }
+    }
+}
+
+/// Unpacks the bitmask of `From<Alert> for u8`, returns the value if other bits are set
+impl TryFrom<u8> for Alert {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Alert::None),
+            1 => Ok(Alert::Low),
+            2 => Ok(Alert::High),
+            3 => Ok(Alert::HighLow),
+            _ => Err(value),
+        }
+    }
+}
+
+/// The alert related fields of the [Configuration] register, read and written together
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AlertConfig {
+    /// What the alert pin reflects
+    pub pin_select: AlertPinSelect,
+
+    /// The polarity of the alert pin
+    pub polarity: Polarity,
+
+    /// How the temperature is compared to the limits
+    pub trigger_mode: TriggerMode,
+}
+
+/// The external circuit of the open drain ALERT pin, to derive the [Polarity] to configure.
+///
+/// | pull up | active high | polarity |
+/// |---------|-------------|----------|
+/// | yes     | no          | [ActiveLow](Polarity::ActiveLow) |
+/// | yes     | yes         | [ActiveHigh](Polarity::ActiveHigh) |
+/// | no      | any         | none, the pin can only be driven low |
+///
+/// The ALERT pin is always open drain, so the circuit is described by the level the firmware expects
+/// and the pull up rather than by a polarity and an open drain flag.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AlertPinConfig {
+    /// True if the firmware expects the pin high when an alert or data ready is signaled, low otherwise
+    pub active_high: bool,
+
+    /// True if the pin is pulled up, externally or by the input of the microcontroller
+    pub pull_up: bool,
+}
+
+impl AlertPinConfig {
+    /// Returns the polarity matching the circuit, `None` if the active level cannot be reached
+    pub fn polarity(&self) -> Option<Polarity> {
+        if !self.pull_up {
+            return None;
+        }
+        if self.active_high {
+            Some(Polarity::ActiveHigh)
+        } else {
+            Some(Polarity::ActiveLow)
+        }
+    }
+}
+
+/// An event reported on the alert pin, depends on the current [AlertPinSelect]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Event {
+    /// New data was ready, contains the temperature
+    DataReady(Celsius),
+
+    /// An alert was triggered
+    Alert(Alert),
+}
+
+/// A temperature with the alert flags read with it, with a fixed size binary format for telemetry
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Measurement {
+    /// The raw two's complement temperature, one lsb is [CELCIUS_CONVERSION]
+    pub raw: i16,
+
+    /// The alert flags
+    pub alert: Alert,
+}
+
+impl Measurement {
+    /// Returns the temperature in celsius
+    pub fn celsius(&self) -> Celsius {
+        Celsius(decode_temperature(self.raw as u16))
+    }
+
+    /// Encodes the measurement in 4 bytes. Bytes 0 and 1 are the raw temperature in big endian,
+    /// byte 2 is the alert bitmask of `From<Alert> for u8` and byte 3 is reserved and always 0
+    pub fn to_bytes(self) -> [u8; 4] {
+        let raw = self.raw.to_be_bytes();
+        [raw[0], raw[1], self.alert.into(), 0]
+    }
+
+    /// Decodes the bytes of [to_bytes](Self::to_bytes), returns `None` if the alert bitmask or the reserved byte are invalid
+    pub fn from_bytes(bytes: [u8; 4]) -> Option<Self> {
+        if bytes[3] != 0 {
+            return None;
+        }
+        Some(Self {
+            raw: i16::from_be_bytes([bytes[0], bytes[1]]),
+            alert: Alert::try_from(bytes[2]).ok()?,
+        })
+    }
+}
+
+/// The continuous config. Can also be used as a profile, see [Tmp117::apply_profile]
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
 pub struct ContinuousConfig {
     /// The average used, will use the one stored in the register if None
     pub average: Average,
@@ -51,202 +255,1220 @@ pub struct ContinuousConfig {
 
     /// The temperature offset used, will use 0 if None
     pub offset: Option<f32>,
+
+    /// The trigger mode used for the comparisons with the limits
+    pub trigger_mode: TriggerMode,
+
+    /// What the alert pin reflects, written with the polarity of `set_alert_pin_config`, active low by default,
+    /// when entering the mode so the pin is in a known state from the first reading. Left as is if None
+    pub pin_select: Option<AlertPinSelect>,
 }
-/// Represents the ID of the device.
+
+/// The raw values of the alert limits, applied with [Tmp117::set_alert_limits]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub struct Id {
-    /// Should always be 0x117
-    pub device: u16,
-    /// Depends on the revision of the device
-    pub revision: u8,
+pub struct AlertLimits {
+    /// The high limit register
+    pub high: HighLimit,
+
+    /// The low limit register
+    pub low: LowLimit,
 }
 
-/// The TMP117 driver. Note that the alert pin is not used in this driver,
-/// see the async implementation if you want the driver to use the alert pin in the drive
-pub struct Tmp117<T, E> {
-    tmp_ll: Tmp117LL<T, E>,
+impl AlertLimits {
+    /// Limits `band` above and below `center`, returns [Error::LimitOutOfRange] if a limit is outside of ±256 °C
+    pub fn try_around<E, PE>(center: Celsius, band: Celsius) -> Result<Self, Error<E, PE>> {
+        Ok(Self {
+            high: HighLimit::try_from_celsius(center.0 + band.0)?,
+            low: LowLimit::try_from_celsius(center.0 - band.0)?,
+        })
+    }
 }
 
-impl<T, E> Tmp117<T, E>
-where
-    T: I2c<SevenBitAddress, Error = E>,
-    E: embedded_hal::i2c::Error + Copy,
-{
-    /// Create a new tmp117 from a i2c bus
-    pub fn new(i2c: T, addr: u8) -> Self {
-        Tmp117::<T, E> {
-            tmp_ll: Tmp117LL::new(i2c, addr),
+/// The exact register values of a configuration, computed at compile time with [ConfigImage::build]
+/// and written with [Tmp117::apply_image]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ConfigImage {
+    /// The configuration register, with the alert pin active low, the default polarity of the drivers
+    pub configuration: u16,
+
+    /// The high limit register
+    pub high_limit: u16,
+
+    /// The low limit register
+    pub low_limit: u16,
+
+    /// The temperature offset register
+    pub offset: u16,
+}
+
+impl ConfigImage {
+    /// Compute the register values of a configuration. Temperatures are in m°C to avoid float math in `const`
+    pub const fn build(
+        mode: ConversionMode,
+        average: Average,
+        conversion: Conversion,
+        trigger_mode: TriggerMode,
+        high_millicelsius: i32,
+        low_millicelsius: i32,
+        offset_millicelsius: i32,
+    ) -> Self {
+        let configuration = (mode as u16) << 10
+            | (conversion as u16) << 7
+            | (average as u16) << 5
+            | (trigger_mode as u16) << 4
+            | (Polarity::ActiveLow as u16) << 3
+            | (AlertPinSelect::Alert as u16) << 2;
+        Self {
+            configuration,
+            high_limit: millicelsius_to_raw(high_millicelsius),
+            low_limit: millicelsius_to_raw(low_millicelsius),
+            offset: millicelsius_to_raw(offset_millicelsius),
         }
     }
+}
 
-    /// Create a new tmp117 from a low level tmp117 driver
-    pub fn new_from_ll(tmp_ll: Tmp117LL<T, E>) -> Self {
-        Tmp117::<T, E> { tmp_ll }
-    }
+// Continuous with 8 averages at 1s, 30 °C and -10 °C limits, checked at compile time
+const _: () = {
+    let image = ConfigImage::build(
+        ConversionMode::Continuous,
+        Average::Avg8,
+        Conversion::Ms1000,
+        TriggerMode::Alert,
+        30_000,
+        -10_000,
+        0,
+    );
+    assert!(image.configuration == 0x0220);
+    assert!(image.high_limit == 0x0F00);
+    assert!(image.low_limit == 0xFB00);
+    assert!(image.offset == 0);
+};
 
-    /// Returns the ID of the device
-    pub fn id(&mut self) -> Result<Id, Error<E>> {
-        let id: DeviceID = self.tmp_ll.read()?;
-        Ok(Id {
+/// The device ID of the TMP117
+pub const DEVICE_ID: u16 = 0x117;
+
+/// Interval between two polls of the eeprom busy flag, programming a word takes about 7ms
+const EEPROM_POLL_US: u32 = 500;
+
+/// Time after which the eeprom is considered stuck busy
+const EEPROM_TIMEOUT_US: u32 = 20_000;
+
+/// Represents the ID of the device. Displayed as `TMP117 rev 1` for example
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Id {
+    /// Should always be 0x117
+    pub device: u16,
+    /// Depends on the revision of the device
+    pub revision: u8,
+}
+
+impl From<DeviceID> for Id {
+    fn from(id: DeviceID) -> Self {
+        Self {
             device: id.device_id().into(),
             revision: id.revision().into(),
-        })
+        }
     }
+}
 
-    fn wait_eeprom(&mut self) -> Result<(), Error<E>> {
-        let mut configuration: Configuration = self.tmp_ll.read()?;
-        while configuration.eeprom_busy() {
-            configuration = self.tmp_ll.read()?;
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.device == DEVICE_ID {
+            write!(f, "TMP117 rev {}", self.revision)
+        } else {
+            write!(f, "device {:#05x} rev {}", self.device, self.revision)
         }
-
-        Ok(())
     }
+}
 
-    fn read_temp_raw(&mut self) -> Result<f32, Error<E>> {
-        let temp: Temperature = self.tmp_ll.read()?;
-
-        // Convert to i16 for two complements
-        let val = (u16::from(temp) as i16) as f32 * CELCIUS_CONVERSION;
-        Ok(val)
+#[cfg(feature = "defmt")]
+impl defmt::Format for Id {
+    fn format(&self, f: defmt::Formatter) {
+        if self.device == DEVICE_ID {
+            defmt::write!(f, "TMP117 rev {}", self.revision)
+        } else {
+            defmt::write!(f, "device {:#x} rev {}", self.device, self.revision)
+        }
     }
+}
 
-    fn check_alert(&mut self) -> Result<Alert, Error<E>> {
-        let config: Configuration = self.tmp_ll.read()?;
-        if config.high_alert() && config.low_alert() {
-            Ok(Alert::HighLow)
-        } else if config.high_alert() {
-            Ok(Alert::High)
-        } else if config.low_alert() {
-            Ok(Alert::Low)
+impl Id {
+    /// Returns an error if the device is not a TMP117, unless any id is accepted
+    fn check<E, PE>(self, accept_any_id: bool) -> Result<Self, Error<E, PE>> {
+        if accept_any_id || self.device == DEVICE_ID {
+            Ok(self)
         } else {
-            Ok(Alert::None)
+            Err(Error::UnexpectedDevice(self))
         }
     }
+}
 
-    fn wait_for_data(&mut self) -> Result<(), Error<E>> {
-        // Loop while the data is not ok
-        loop {
-            let config: Configuration = self.tmp_ll.read()?;
-            if config.data_ready() {
-                break;
-            }
+/// Result of [Tmp117::diagnose], displayed as a message for the startup logs
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Diagnosis {
+    /// A TMP117 answered with this revision
+    Ok {
+        /// The revision of the device
+        revision: u8,
+    },
+
+    /// No device acknowledged the address
+    NoDevice,
+
+    /// A device answered but it is not a TMP117
+    WrongDevice {
+        /// The ID reported by the device
+        id: Id,
+    },
+
+    /// The bus failed for another reason
+    BusFault(ErrorKind),
+}
+
+impl Diagnosis {
+    fn from_id<E, PE>(id: Result<Id, Error<E, PE>>) -> Self
+    where
+        E: embedded_hal::i2c::Error,
+    {
+        match id {
+            Ok(id) if id.device == DEVICE_ID => Diagnosis::Ok {
+                revision: id.revision,
+            },
+            Ok(id) => Diagnosis::WrongDevice { id },
+            Err(Error::Bus(e)) => match e.kind() {
+                ErrorKind::NoAcknowledge(
+                    NoAcknowledgeSource::Address | NoAcknowledgeSource::Unknown,
+                ) => Diagnosis::NoDevice,
+                kind => Diagnosis::BusFault(kind),
+            },
+            Err(_) => Diagnosis::BusFault(ErrorKind::Other),
         }
-        Ok(())
     }
+}
 
-    fn wait_for_alert(&mut self) -> Result<Alert, Error<E>> {
-        loop {
-            let alert = self.check_alert();
-            if let Ok(Alert::None) = alert {
-                continue;
-            } else {
-                return alert;
-            }
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnosis::Ok { revision } => write!(f, "TMP117 rev {} found", revision),
+            Diagnosis::NoDevice => f.write_str("no device at the address"),
+            Diagnosis::WrongDevice { id } => write!(f, "unexpected {}", id),
+            Diagnosis::BusFault(kind) => write!(f, "bus fault: {}", kind),
         }
     }
+}
 
-    fn set_continuous(
-        &mut self,
-        config: ContinuousConfig,
-    ) -> Result<ContinuousHandler<'_, T, E>, Error<E>> {
-        if let Some(val) = config.high {
-            let high: HighLimit = ((val / CELCIUS_CONVERSION) as u16).into();
-            self.tmp_ll.write(high)?;
-        }
-        if let Some(val) = config.low {
-            let low: LowLimit = ((val / CELCIUS_CONVERSION) as u16).into();
-            self.tmp_ll.write(low)?;
-        }
-        if let Some(val) = config.offset {
-            let off: TemperatureOffset = ((val / CELCIUS_CONVERSION) as u16).into();
-            self.tmp_ll.write(off)?;
-        }
+/// Snapshot of the configuration of the device, with the limits and offset decoded.
+/// With the `serde` feature, it can be sent with a compact format such as postcard, the configuration is serialized as its raw `u16`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ConfigSnapshot {
+    /// The configuration register
+    #[cfg_attr(feature = "serde", serde(with = "serde_configuration"))]
+    pub configuration: Configuration,
 
-        self.tmp_ll.edit(|r: &mut Configuration| {
-            r.set_mode(ConversionMode::Continuous);
-            r.set_polarity(Polarity::ActiveLow);
-            r.set_average(config.average);
-            r.set_conversion(config.conversion);
-        })?;
+    /// The high limit
+    pub high_limit: Celsius,
 
-        Ok(ContinuousHandler { tmp117: self })
-    }
+    /// The low limit
+    pub low_limit: Celsius,
 
-    fn set_oneshot(&mut self, average: Average) -> Result<(), Error<E>> {
-        self.tmp_ll.edit(|r: &mut Configuration| {
-            r.set_mode(ConversionMode::OneShot);
-            r.set_polarity(Polarity::ActiveLow);
-            r.set_average(average);
-        })?;
-        Ok(())
-    }
+    /// The temperature offset
+    pub offset: Celsius,
+}
 
-    fn set_shutdown(&mut self) -> Result<(), Error<E>> {
-        self.tmp_ll.edit(|r: &mut Configuration| {
-            r.set_mode(ConversionMode::Shutdown);
-        })?;
-        Ok(())
+/// The raw configuration of a device, to copy it to other devices.
+/// Read with [Tmp117::export_config] and written with [Tmp117::import_config]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DeviceConfig {
+    /// The configuration register
+    pub configuration: Configuration,
+
+    /// The high limit register
+    pub high_limit: HighLimit,
+
+    /// The low limit register
+    pub low_limit: LowLimit,
+
+    /// The temperature offset register
+    pub offset: TemperatureOffset,
+
+    /// The user eeprom, not copied if None
+    pub user_eeprom: Option<[u16; 3]>,
+}
+
+/// Serializes the [Configuration] register as its raw value
+#[cfg(feature = "serde")]
+mod serde_configuration {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+
+    use crate::register::Configuration;
+
+    pub fn serialize<S: Serializer>(
+        config: &Configuration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(u16::from(*config))
     }
 
-    /// Resets the device and put it in shutdown
-    pub fn reset<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
-    where
-        D: DelayNs,
-    {
-        self.tmp_ll.edit(|r: &mut Configuration| {
-            r.set_reset(true);
-        })?;
-        delay.delay_ms(2);
-        self.set_shutdown()?;
-        Ok(())
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Configuration, D::Error> {
+        let raw = u16::deserialize(deserializer)?;
+        Configuration::try_from(raw).map_err(|_| D::Error::custom("invalid configuration"))
     }
+}
 
-    /// Write data to user eeprom. Note that this is blocking because we wait for write on the eeprom to complete
-    pub fn write_eeprom(&mut self, values: [u16; 3]) -> Result<(), Error<E>> {
-        self.wait_eeprom()?;
-        self.tmp_ll.write(UEEPROM1::from(values[0]))?;
+/// A monotonic clock used to timestamp the readings, wrapping the instant of the runtime for example.
+/// Enabled with the `clock` feature
+#[cfg(feature = "clock")]
+pub trait Clock {
+    /// The instant returned by the clock
+    type Instant;
 
-        self.wait_eeprom()?;
-        self.tmp_ll.write(UEEPROM2::from(values[1]))?;
+    /// Returns the current instant
+    fn now(&self) -> Self::Instant;
+}
 
-        self.wait_eeprom()?;
-        self.tmp_ll.write(UEEPROM3::from(values[2]))?;
+/// A value with the instant at which it was read
+#[cfg(feature = "clock")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Timestamped<T, I> {
+    /// The value read
+    pub value: T,
 
-        Ok(())
+    /// The instant of the read
+    pub at: I,
+}
+
+/// Estimates the number of conversions missed between two reads in continuous mode.
+/// The device has no conversion counter, so it is computed from the conversion period and the elapsed time provided by the caller
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SkipEstimator {
+    period_ms: u32,
+}
+
+impl SkipEstimator {
+    /// Create a new estimator for the conversion and average used in continuous mode
+    pub const fn new(conversion: Conversion, average: Average) -> Self {
+        Self {
+            period_ms: conversion_period_ms(conversion, average),
+        }
     }
 
-    /// Read the data from the eeprom
-    pub fn read_eeprom(&mut self) -> Result<[u16; 3], Error<E>> {
-        let u1: UEEPROM1 = self.tmp_ll.read()?;
-        let u2: UEEPROM2 = self.tmp_ll.read()?;
-        let u3: UEEPROM3 = self.tmp_ll.read()?;
+    /// Returns the estimated number of conversions that were never read,
+    /// given the time in milliseconds elapsed between two reads
+    pub const fn skipped(&self, elapsed_ms: u32) -> u32 {
+        (elapsed_ms / self.period_ms).saturating_sub(1)
+    }
+}
 
-        Ok([u1.into(), u2.into(), u3.into()])
+/// Opt-in fixed delay between reads, for slow or long buses that should not be hammered.
+/// Owns the delay used to wait, see [read_temp_delayed](ContinuousHandler::read_temp_delayed).
+/// There is no clock, so the full interval is waited before every successful read but the first,
+/// however much time already passed. Reads done without it, with [read_temp](ContinuousHandler::read_temp) for example, are not delayed
+#[derive(Debug)]
+pub struct ReadDelay<D> {
+    delay: D,
+    interval_us: u32,
+    pending: bool,
+}
+
+impl<D> ReadDelay<D> {
+    /// Create a new delay of `interval_us` microseconds between reads
+    pub const fn new(delay: D, interval_us: u32) -> Self {
+        Self {
+            delay,
+            interval_us,
+            pending: false,
+        }
     }
 
-    /// Wait for data and read the temperature in celsius and shutdown since it's a oneshot
-    pub fn oneshot(&mut self, average: Average) -> Result<f32, Error<E>> {
-        self.set_oneshot(average)?;
-        self.wait_for_data()?;
-        let data = self.read_temp_raw()?;
-        Ok(data)
+    /// Forget the last read so the next one is not delayed
+    pub fn reset(&mut self) {
+        self.pending = false;
     }
 
-    /// Pass a config and closure for the continuous mode.
-    /// The device gets set to continuous, then the function is called with the handler
-    /// and finally the device is shutdown
-    pub fn continuous<F>(&mut self, config: ContinuousConfig, f: F) -> Result<(), Error<E>>
-    where
-        F: FnOnce(ContinuousHandler<'_, T, E>) -> Result<(), Error<E>>,
-    {
-        let handler = self.set_continuous(config)?;
-        f(handler)?;
-        self.set_shutdown()
+    /// Release the delay
+    pub fn release(self) -> D {
+        self.delay
     }
 }
 
-/// Handler for the continuous mode
+/// Progress of a write to the user eeprom.
+/// Keeps track of the words already written so an interrupted write can be resumed instead of restarted
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EepromWrite {
+    values: [u16; 3],
+    written: usize,
+}
+
+impl EepromWrite {
+    /// Create a new write of the values to the user eeprom
+    pub fn new(values: [u16; 3]) -> Self {
+        Self { values, written: 0 }
+    }
+
+    /// Create a new write of the values to the [UEEPROM2] and [UEEPROM3] words only,
+    /// leaving [UEEPROM1] and its NIST traceability ID untouched
+    pub fn user_words(values: [u16; 2]) -> Self {
+        Self {
+            values: [0, values[0], values[1]],
+            written: 1,
+        }
+    }
+
+    /// Returns the number of words already written, a skipped [UEEPROM1] counts as written
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Returns true if all the words were written
+    pub fn is_done(&self) -> bool {
+        self.written == self.values.len()
+    }
+}
+
+/// Compares the user eeprom read back to the expected values
+fn check_eeprom<E, PE>(expected: [u16; 3], got: [u16; 3]) -> Result<(), Error<E, PE>> {
+    for (index, (expected, got)) in expected.into_iter().zip(got).enumerate() {
+        if expected != got {
+            return Err(Error::EepromVerifyFailed {
+                index,
+                expected,
+                got,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Hysteresis applied by the `control_state` methods
+fn heat_on(temp: f32, setpoint: Celsius, hysteresis: Celsius, prev: bool) -> bool {
+    if temp < setpoint.0 - hysteresis.0 {
+        true
+    } else if temp > setpoint.0 + hysteresis.0 {
+        false
+    } else {
+        prev
+    }
+}
+
+/// A layout for the user eeprom: a magic word identifying the application, a version and a payload.
+/// Note that the first word holds the NIST traceability ID from the factory, it is lost once written
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct UserStore {
+    /// Identifies the application that wrote the eeprom, in [UEEPROM1]
+    pub magic: u16,
+
+    /// Version of the payload layout, in [UEEPROM2]
+    pub version: u16,
+
+    /// The application data, in [UEEPROM3]
+    pub payload: u16,
+}
+
+impl UserStore {
+    /// Returns true if the eeprom was written by the application using this magic word
+    pub fn validate(&self, magic: u16) -> bool {
+        self.magic == magic
+    }
+
+    /// Returns the words to write with [Tmp117::write_eeprom]
+    pub fn to_words(&self) -> [u16; 3] {
+        [self.magic, self.version, self.payload]
+    }
+}
+
+impl From<[u16; 3]> for UserStore {
+    fn from(words: [u16; 3]) -> Self {
+        Self {
+            magic: words[0],
+            version: words[1],
+            payload: words[2],
+        }
+    }
+}
+
+/// The TMP117 driver. Note that the alert pin is not used in this driver,
+/// see the async implementation if you want the driver to use the alert pin in the drive
+pub struct Tmp117<T, E> {
+    tmp_ll: Tmp117LL<T, E>,
+    polarity: Polarity,
+    eeprom_programming: bool,
+}
+
+impl<T, E> Tmp117<T, E>
+where
+    T: I2c<SevenBitAddress, Error = E>,
+    E: embedded_hal::i2c::Error + Copy,
+{
+    /// Create a new tmp117 from a i2c bus
+    pub fn new(i2c: T, addr: u8) -> Self {
+        Tmp117::<T, E> {
+            tmp_ll: Tmp117LL::new(i2c, addr),
+            polarity: Polarity::ActiveLow,
+            eeprom_programming: false,
+        }
+    }
+
+    /// Create a new tmp117 from a low level tmp117 driver
+    pub fn new_from_ll(tmp_ll: Tmp117LL<T, E>) -> Self {
+        Tmp117::<T, E> {
+            tmp_ll,
+            polarity: Polarity::ActiveLow,
+            eeprom_programming: false,
+        }
+    }
+
+    /// Create a new tmp117 from a i2c bus and verify the device ID.
+    /// Set `accept_any_id` for register compatible parts reporting another ID, the ID is still read and returned
+    pub fn new_checked(i2c: T, addr: u8, accept_any_id: bool) -> Result<(Self, Id), Error<E>> {
+        let mut tmp = Self::new(i2c, addr);
+        let id = tmp.id()?.check(accept_any_id)?;
+        Ok((tmp, id))
+    }
+
+    /// Create a new tmp117 from a i2c bus without resetting or reconfiguring the device.
+    /// Returns the driver with the conversion mode the device is currently in,
+    /// useful to take over a device already configured by a bootloader for example
+    pub fn adopt(i2c: T, addr: u8) -> Result<(Self, ConversionMode), Error<E>> {
+        let mut tmp = Self::new(i2c, addr);
+        let config: Configuration = tmp.tmp_ll.read()?;
+        Ok((tmp, config.mode()))
+    }
+
+    /// Returns the ID of the device
+    pub fn id(&mut self) -> Result<Id, Error<E>> {
+        let id: DeviceID = self.tmp_ll.read()?;
+        Ok(id.into())
+    }
+
+    /// Returns the ID of the device, or `None` if no device acknowledged the address.
+    /// Other bus errors are still returned, useful to scan for devices
+    pub fn probe(&mut self) -> Result<Option<Id>, Error<E>> {
+        match self.id() {
+            Ok(id) => Ok(Some(id)),
+            Err(Error::Bus(e))
+                if matches!(
+                    e.kind(),
+                    ErrorKind::NoAcknowledge(
+                        NoAcknowledgeSource::Address | NoAcknowledgeSource::Unknown
+                    )
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read the device ID and tell if the device is missing, is not a TMP117 or if the bus failed, in a single call
+    pub fn diagnose(&mut self) -> Diagnosis {
+        Diagnosis::from_id(self.id())
+    }
+
+    /// Read a snapshot of the configuration, limits and offset of the device
+    pub fn snapshot(&mut self) -> Result<ConfigSnapshot, Error<E>> {
+        let configuration: Configuration = self.tmp_ll.read()?;
+        let high: HighLimit = self.tmp_ll.read()?;
+        let low: LowLimit = self.tmp_ll.read()?;
+        let offset: TemperatureOffset = self.tmp_ll.read()?;
+        Ok(ConfigSnapshot {
+            configuration,
+            high_limit: decode_temperature(high.into()).into(),
+            low_limit: decode_temperature(low.into()).into(),
+            offset: decode_temperature(offset.into()).into(),
+        })
+    }
+
+    /// Read the configuration, limits and offset, and the user eeprom if `with_eeprom` is set,
+    /// to copy them to another device with [import_config](Self::import_config)
+    pub fn export_config(&mut self, with_eeprom: bool) -> Result<DeviceConfig, Error<E>> {
+        let user_eeprom = if with_eeprom {
+            Some(self.read_eeprom()?)
+        } else {
+            None
+        };
+        Ok(DeviceConfig {
+            configuration: self.tmp_ll.read()?,
+            high_limit: self.tmp_ll.read()?,
+            low_limit: self.tmp_ll.read()?,
+            offset: self.tmp_ll.read()?,
+            user_eeprom,
+        })
+    }
+
+    /// Write a configuration read from another device with [export_config](Self::export_config).
+    /// The user eeprom is written first if present, which needs the device to be in shutdown,
+    /// returns [Error::BusyConverting] otherwise
+    pub fn import_config<D>(&mut self, config: &DeviceConfig, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.check_eeprom_locked()?;
+        if let Some(values) = config.user_eeprom {
+            self.write_eeprom(values, delay)?;
+        }
+        self.tmp_ll.write(config.high_limit)?;
+        self.tmp_ll.write(config.low_limit)?;
+        self.tmp_ll.write(config.offset)?;
+
+        let configuration =
+            Configuration::try_from(u16::from(config.configuration) & CONFIGURATION_WRITABLE_MASK)
+                .map_err(|_| Error::InvalidData)?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| *r = configuration)?;
+        Ok(())
+    }
+
+    /// Returns the conversion period in milliseconds of the average and conversion currently configured,
+    /// useful after [adopt](Self::adopt) to know the cadence of a device configured by someone else.
+    /// Note that reading the configuration clears the data ready flag
+    pub fn current_conversion_period_ms(&mut self) -> Result<u32, Error<E>> {
+        let config: Configuration = self.tmp_ll.read()?;
+        Ok(conversion_period_ms(config.conversion(), config.average()))
+    }
+
+    /// Returns an upper bound of the time in milliseconds until the next data is ready,
+    /// based on the current mode, average and conversion. Returns 0 if the data is already ready.
+    /// In shutdown, returns the time a oneshot conversion would take.
+    /// Note that reading the configuration clears the data ready flag
+    pub fn time_to_data_ready(&mut self) -> Result<u32, Error<E>> {
+        let config: Configuration = self.tmp_ll.read()?;
+        if config.data_ready() {
+            return Ok(0);
+        }
+
+        let period = match config.mode() {
+            ConversionMode::Continuous => {
+                conversion_period_ms(config.conversion(), config.average())
+            }
+            ConversionMode::OneShot | ConversionMode::Shutdown => {
+                conversion_period_ms(Conversion::Ms15_5, config.average())
+            }
+        };
+        Ok(period)
+    }
+
+    /// Returns true if the eeprom is busy, either programming or loading at power-up.
+    /// Uses the flag of the [Configuration] register, which is the one the driver relies on.
+    /// The [EEPROM] register flag mirrors it
+    pub fn eeprom_busy(&mut self) -> Result<bool, Error<E>> {
+        let configuration: Configuration = self.tmp_ll.read()?;
+        Ok(configuration.eeprom_busy())
+    }
+
+    fn wait_eeprom<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        for _ in 0..EEPROM_TIMEOUT_US / EEPROM_POLL_US {
+            if !self.eeprom_busy()? {
+                return Ok(());
+            }
+            delay.delay_us(EEPROM_POLL_US);
+        }
+        Err(Error::EepromTimeout)
+    }
+
+    fn read_temp_raw(&mut self) -> Result<f32, Error<E>> {
+        let raw = self.tmp_ll.read_temperature_raw()?;
+        Ok(decode_temperature(raw as u16))
+    }
+
+    fn check_alert(&mut self) -> Result<Alert, Error<E>> {
+        let config: Configuration = self.tmp_ll.read()?;
+        Ok(Alert::from_config(&config))
+    }
+
+    fn wait_for_data(&mut self) -> Result<(), Error<E>> {
+        // Loop while the data is not ok
+        loop {
+            let config: Configuration = self.tmp_ll.read()?;
+            if config.data_ready() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn wait_for_alert(&mut self) -> Result<Alert, Error<E>> {
+        loop {
+            let alert = self.check_alert();
+            if let Ok(Alert::None) = alert {
+                continue;
+            } else {
+                return alert;
+            }
+        }
+    }
+
+    fn apply_config(
+        &mut self,
+        config: &ContinuousConfig,
+        mode: ConversionMode,
+    ) -> Result<(), Error<E>> {
+        if let Some(val) = config.high {
+            self.tmp_ll.write(HighLimit::try_from_celsius(val)?)?;
+        }
+        if let Some(val) = config.low {
+            self.tmp_ll.write(LowLimit::try_from_celsius(val)?)?;
+        }
+        if let Some(val) = config.offset {
+            self.tmp_ll
+                .write(TemperatureOffset::try_from_celsius(val)?)?;
+        }
+
+        let polarity = self.polarity;
+        self.tmp_ll.edit(|r: &mut Configuration| {
+            r.set_mode(mode);
+            r.set_polarity(polarity);
+            r.set_average(config.average);
+            r.set_conversion(config.conversion);
+            r.set_trigger_mode(config.trigger_mode);
+            if let Some(select) = config.pin_select {
+                r.set_dr_alert(select);
+            }
+        })?;
+        Ok(())
+    }
+
+    fn set_continuous(
+        &mut self,
+        config: ContinuousConfig,
+    ) -> Result<ContinuousHandler<'_, T, E>, Error<E>> {
+        self.check_eeprom_locked()?;
+        self.apply_config(&config, ConversionMode::Continuous)?;
+        Ok(ContinuousHandler { tmp117: self })
+    }
+
+    fn set_oneshot(
+        &mut self,
+        average: Average,
+        conversion: Option<Conversion>,
+    ) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        let polarity = self.polarity;
+        self.tmp_ll.edit(|r: &mut Configuration| {
+            r.set_mode(ConversionMode::OneShot);
+            r.set_polarity(polarity);
+            r.set_average(average);
+            if let Some(conversion) = conversion {
+                r.set_conversion(conversion);
+            }
+        })?;
+        Ok(())
+    }
+
+    fn set_shutdown(&mut self) -> Result<(), Error<E>> {
+        self.tmp_ll.edit(|r: &mut Configuration| {
+            r.set_mode(ConversionMode::Shutdown);
+        })?;
+        Ok(())
+    }
+
+    /// Allow the setters to write while the eeprom is unlocked, which also programs the eeprom.
+    /// By default, the setters and the methods changing the conversion mode return [Error::EepromUnlocked]
+    /// to avoid wearing the eeprom by accident
+    pub fn allow_eeprom_programming(&mut self, allow: bool) {
+        self.eeprom_programming = allow;
+    }
+
+    fn check_eeprom_locked(&mut self) -> Result<(), Error<E>> {
+        if self.eeprom_programming {
+            return Ok(());
+        }
+
+        let eeprom: EEPROM = self.tmp_ll.read()?;
+        if eeprom.unlock() {
+            return Err(Error::EepromUnlocked);
+        }
+        Ok(())
+    }
+
+    /// Select what the alert pin reflects, either the alert flags or the data ready flag.
+    /// The polarity is set to the one of [set_alert_pin_config](Self::set_alert_pin_config), active low by default
+    pub fn set_alert_pin_select(&mut self, select: AlertPinSelect) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        let polarity = self.polarity;
+        self.tmp_ll.edit(|r: &mut Configuration| {
+            r.set_dr_alert(select);
+            r.set_polarity(polarity);
+        })?;
+        Ok(())
+    }
+
+    /// Returns what the alert pin currently reflects
+    pub fn get_alert_pin_select(&mut self) -> Result<AlertPinSelect, Error<E>> {
+        let config: Configuration = self.tmp_ll.read()?;
+        Ok(config.dr_alert())
+    }
+
+    /// Returns [Error::SampleRateTooHigh] if the configured conversion cycle is shorter than the time
+    /// the caller needs to service each data, in which case samples would be silently missed
+    pub fn warn_if_too_fast(&mut self, min_service_ms: u32) -> Result<(), Error<E>> {
+        let config: Configuration = self.tmp_ll.read()?;
+        if conversion_period_ms(config.conversion(), config.average()) < min_service_ms {
+            return Err(Error::SampleRateTooHigh);
+        }
+        Ok(())
+    }
+
+    /// Set the high limit from the raw two's complement register value, one lsb is [CELCIUS_CONVERSION]
+    pub fn set_high_limit_raw(&mut self, raw: i16) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        self.tmp_ll.write(HighLimit::from(raw as u16))?;
+        Ok(())
+    }
+
+    /// Returns the raw two's complement value of the high limit, one lsb is [CELCIUS_CONVERSION]
+    pub fn high_limit_raw(&mut self) -> Result<i16, Error<E>> {
+        let high: HighLimit = self.tmp_ll.read()?;
+        Ok(u16::from(high) as i16)
+    }
+
+    /// Set the low limit from the raw two's complement register value, one lsb is [CELCIUS_CONVERSION]
+    pub fn set_low_limit_raw(&mut self, raw: i16) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        self.tmp_ll.write(LowLimit::from(raw as u16))?;
+        Ok(())
+    }
+
+    /// Returns the raw two's complement value of the low limit, one lsb is [CELCIUS_CONVERSION]
+    pub fn low_limit_raw(&mut self) -> Result<i16, Error<E>> {
+        let low: LowLimit = self.tmp_ll.read()?;
+        Ok(u16::from(low) as i16)
+    }
+
+    /// Returns the raw value of the configuration register, before decoding
+    pub fn read_config_raw(&mut self) -> Result<u16, Error<E>> {
+        let val = self.tmp_ll.read_raw(Configuration::ADDRESS)?;
+        Ok(val)
+    }
+
+    /// Writes the raw value to the configuration register.
+    /// Only the bits of [CONFIGURATION_WRITABLE_MASK] are written, the read only bits are ignored
+    pub fn write_config_raw(&mut self, value: u16) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        let config = Configuration::try_from(value & CONFIGURATION_WRITABLE_MASK)
+            .map_err(|_| Error::InvalidData)?;
+        self.tmp_ll.edit(|r: &mut Configuration| *r = config)?;
+        Ok(())
+    }
+
+    /// Configure the device to only drive the alert pin in thermal mode, the pin goes active above `high`
+    /// and back inactive below `high - hysteresis`. The device is left converting in continuous mode.
+    /// Watch the pin with a gpio to never read the temperature over i2c, the bus stays idle
+    /// and the host can sleep until the pin changes, which saves most of the power of the system
+    pub fn arm_thermal_alert(
+        &mut self,
+        high: Celsius,
+        hysteresis: Celsius,
+        polarity: Polarity,
+    ) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        self.tmp_ll.write(HighLimit::try_from_celsius(high.0)?)?;
+        self.tmp_ll
+            .write(LowLimit::try_from_celsius(high.0 - hysteresis.0)?)?;
+        self.tmp_ll.edit(|r: &mut Configuration| {
+            r.set_mode(ConversionMode::Continuous);
+            r.set_trigger_mode(TriggerMode::Thermal);
+            r.set_dr_alert(AlertPinSelect::Alert);
+            r.set_polarity(polarity);
+        })?;
+        self.polarity = polarity;
+        Ok(())
+    }
+
+    /// Read the alert pin selection, polarity and trigger mode in a single transaction.
+    /// Note that reading the configuration clears the alert and data ready flags
+    pub fn read_alert_config(&mut self) -> Result<AlertConfig, Error<E>> {
+        let config: Configuration = self.tmp_ll.read()?;
+        Ok(AlertConfig {
+            pin_select: config.dr_alert(),
+            polarity: config.polarity(),
+            trigger_mode: config.trigger_mode(),
+        })
+    }
+
+    /// Write the alert pin selection, polarity and trigger mode with a single read-modify-write of the configuration
+    pub fn write_alert_config(&mut self, alert: &AlertConfig) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        self.tmp_ll.edit(|r: &mut Configuration| {
+            r.set_dr_alert(alert.pin_select);
+            r.set_polarity(alert.polarity);
+            r.set_trigger_mode(alert.trigger_mode);
+        })?;
+        self.polarity = alert.polarity;
+        Ok(())
+    }
+
+    /// Set the polarity of the alert pin to match the circuit described, see [AlertPinConfig].
+    /// Returns [Error::UnreachablePolarity] if the circuit cannot reach the active level.
+    /// The driver keeps the polarity, it is written again when selecting the pin function or changing the conversion mode
+    pub fn set_alert_pin_config(&mut self, config: &AlertPinConfig) -> Result<(), Error<E>> {
+        let polarity = config.polarity().ok_or(Error::UnreachablePolarity)?;
+        self.check_eeprom_locked()?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| r.set_polarity(polarity))?;
+        self.polarity = polarity;
+        Ok(())
+    }
+
+    /// Write the high and low alert limits
+    pub fn set_alert_limits(&mut self, limits: AlertLimits) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        self.tmp_ll.write(limits.high)?;
+        self.tmp_ll.write(limits.low)?;
+        Ok(())
+    }
+
+    /// Write a configuration image built with [ConfigImage::build]. The read only bits of the configuration are ignored
+    pub fn apply_image(&mut self, image: &ConfigImage) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        self.tmp_ll.write(HighLimit::from(image.high_limit))?;
+        self.tmp_ll.write(LowLimit::from(image.low_limit))?;
+        self.tmp_ll.write(TemperatureOffset::from(image.offset))?;
+
+        let configuration =
+            Configuration::try_from(image.configuration & CONFIGURATION_WRITABLE_MASK)
+                .map_err(|_| Error::InvalidData)?;
+        self.tmp_ll
+            .edit(|r: &mut Configuration| *r = configuration)?;
+        Ok(())
+    }
+
+    /// Resets the device and put it in shutdown
+    pub fn reset<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.tmp_ll.edit(|r: &mut Configuration| {
+            r.set_reset(true);
+        })?;
+        delay.delay_ms(2);
+        self.set_shutdown()?;
+        Ok(())
+    }
+
+    /// Resets the device and return the first valid temperature in celsius.
+    /// Reading right after a reset returns the -256 °C power up value until the first conversion,
+    /// so this waits for the reset and the eeprom load, then does a oneshot with the average loaded from the eeprom.
+    /// The device is in shutdown afterwards
+    pub fn wait_ready_after_reset<D>(&mut self, delay: &mut D) -> Result<f32, Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.reset(delay)?;
+        self.wait_eeprom(delay)?;
+        let config: Configuration = self.tmp_ll.read()?;
+        self.oneshot(config.average())
+    }
+
+    /// Apply the settings of the profile without changing the conversion mode, only the registers that differ are written.
+    /// Allows to switch cheaply between profiles, a fast and a slow one for example
+    pub fn apply_profile(&mut self, profile: &ContinuousConfig) -> Result<(), Error<E>> {
+        self.check_eeprom_locked()?;
+        if let Some(val) = profile.high {
+            let new = HighLimit::try_from_celsius(val)?;
+            let high: HighLimit = self.tmp_ll.read()?;
+            if high != new {
+                self.tmp_ll.write(new)?;
+            }
+        }
+        if let Some(val) = profile.low {
+            let new = LowLimit::try_from_celsius(val)?;
+            let low: LowLimit = self.tmp_ll.read()?;
+            if low != new {
+                self.tmp_ll.write(new)?;
+            }
+        }
+        if let Some(val) = profile.offset {
+            let new = TemperatureOffset::try_from_celsius(val)?;
+            let offset: TemperatureOffset = self.tmp_ll.read()?;
+            if offset != new {
+                self.tmp_ll.write(new)?;
+            }
+        }
+
+        let current: Configuration = self.tmp_ll.read()?;
+        let mut config = current;
+        config.set_average(profile.average);
+        config.set_conversion(profile.conversion);
+        config.set_trigger_mode(profile.trigger_mode);
+        if config != current {
+            self.tmp_ll.write_register(&config)?;
+        }
+        Ok(())
+    }
+
+    /// Recover to a known good state, after detecting a corruption for example.
+    /// Resets the device, verifies its ID and applies the config, leaving the device in shutdown
+    pub fn recover<D>(&mut self, delay: &mut D, config: ContinuousConfig) -> Result<Id, Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.check_eeprom_locked()?;
+        self.reset(delay)?;
+        let id = self.id()?.check(false)?;
+        self.apply_config(&config, ConversionMode::Shutdown)?;
+        Ok(id)
+    }
+
+    /// Write data to user eeprom. Note that this is blocking because we wait for write on the eeprom to complete,
+    /// the delay is used between the polls of the busy flag.
+    /// The device must be in shutdown, returns [Error::BusyConverting] otherwise
+    pub fn write_eeprom<D>(&mut self, values: [u16; 3], delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.resume_eeprom_write(&mut EepromWrite::new(values), delay)
+    }
+
+    /// Write data to the [UEEPROM2] and [UEEPROM3] words of the user eeprom, [UEEPROM1] is never written to keep the NIST ID.
+    /// The device must be in shutdown, returns [Error::BusyConverting] otherwise
+    pub fn write_user_words<D>(&mut self, values: [u16; 2], delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.resume_eeprom_write(&mut EepromWrite::user_words(values), delay)
+    }
+
+    /// Write the remaining words of the eeprom write, the progress is updated after each word.
+    /// The eeprom is unlocked for the write and locked again once the last word is programmed.
+    /// Returns [Error::BusyConverting] if the device is not in shutdown.
+    /// If interrupted by an error, the eeprom may stay unlocked, calling it again resumes the write.
+    /// Returns [Error::EepromTimeout] if the eeprom stays busy
+    pub fn resume_eeprom_write<D>(
+        &mut self,
+        write: &mut EepromWrite,
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        // Programming while converting can interact with the eeprom controller
+        let config: Configuration = self.tmp_ll.read()?;
+        if config.mode() != ConversionMode::Shutdown {
+            return Err(Error::BusyConverting);
+        }
+
+        // Writes to the user eeprom registers only program the eeprom while it is unlocked
+        self.tmp_ll.edit(|r: &mut EEPROM| r.set_unlock(true))?;
+        while !write.is_done() {
+            self.wait_eeprom(delay)?;
+            let value = write.values[write.written];
+            match write.written {
+                0 => self.tmp_ll.write(UEEPROM1::from(value))?,
+                1 => self.tmp_ll.write(UEEPROM2::from(value))?,
+                _ => self.tmp_ll.write(UEEPROM3::from(value))?,
+            }
+            write.written += 1;
+        }
+
+        self.wait_eeprom(delay)?;
+        self.tmp_ll.edit(|r: &mut EEPROM| r.set_unlock(false))?;
+        Ok(())
+    }
+
+    /// Read the user eeprom with the [UserStore] layout, use [UserStore::validate] to check its magic word
+    pub fn read_user_eeprom_typed(&mut self) -> Result<UserStore, Error<E>> {
+        Ok(self.read_eeprom()?.into())
+    }
+
+    /// Read back the user eeprom and compare it to the expected values,
+    /// returns [Error::EepromVerifyFailed] with the first word that differs
+    pub fn verify_eeprom(&mut self, expected: [u16; 3]) -> Result<(), Error<E>> {
+        let got = self.read_eeprom()?;
+        check_eeprom(expected, got)
+    }
+
+    /// Same as [write_eeprom](Self::write_eeprom), then verify the values with [verify_eeprom](Self::verify_eeprom),
+    /// since programming can fail with a marginal supply. The device is reset before verifying so the words
+    /// are reloaded from the eeprom, which also reloads the configuration, limits and offset stored in it.
+    /// The device is left in shutdown
+    pub fn write_eeprom_verified<D>(
+        &mut self,
+        values: [u16; 3],
+        delay: &mut D,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.write_eeprom(values, delay)?;
+        self.reset(delay)?;
+        self.wait_eeprom(delay)?;
+        self.verify_eeprom(values)
+    }
+
+    /// Read the data from the eeprom. Reading is allowed in any mode, only the writes check at runtime
+    /// that the device is in shutdown since the driver does not track the mode in its type
+    pub fn read_eeprom(&mut self) -> Result<[u16; 3], Error<E>> {
+        let u1: UEEPROM1 = self.tmp_ll.read()?;
+        let u2: UEEPROM2 = self.tmp_ll.read()?;
+        let u3: UEEPROM3 = self.tmp_ll.read()?;
+
+        Ok([u1.into(), u2.into(), u3.into()])
+    }
+
+    /// Wait for data and read the temperature in celsius. The device goes back to shutdown by itself since it's a oneshot,
+    /// returns [Error::UnexpectedMode] if it did not
+    pub fn oneshot(&mut self, average: Average) -> Result<f32, Error<E>> {
+        self.oneshot_inner(average, None)
+    }
+
+    /// Same as [oneshot](Self::oneshot), but also writes the conversion cycle, which affects the oneshot timing
+    pub fn oneshot_with(
+        &mut self,
+        average: Average,
+        conversion: Conversion,
+    ) -> Result<f32, Error<E>> {
+        self.oneshot_inner(average, Some(conversion))
+    }
+
+    fn oneshot_inner(
+        &mut self,
+        average: Average,
+        conversion: Option<Conversion>,
+    ) -> Result<f32, Error<E>> {
+        self.set_oneshot(average, conversion)?;
+        self.wait_for_data()?;
+        let data = self.read_temp_raw()?;
+
+        let config: Configuration = self.tmp_ll.read()?;
+        if config.mode() != ConversionMode::Shutdown {
+            return Err(Error::UnexpectedMode);
+        }
+        Ok(data)
+    }
+
+    /// Sample with oneshots aligned to each whole second of the clock, which returns milliseconds.
+    /// The conversion is started early by its duration so the data is ready at the boundary,
+    /// `on_sample` is called with the temperature in celsius and the boundary until it returns [ControlFlow::Break]
+    #[cfg(feature = "clock")]
+    pub fn sample_aligned<C, D, F>(
+        &mut self,
+        clock: &C,
+        delay: &mut D,
+        average: Average,
+        mut on_sample: F,
+    ) -> Result<(), Error<E>>
+    where
+        C: Clock<Instant = u64>,
+        D: DelayNs,
+        F: FnMut(Timestamped<f32, u64>) -> ControlFlow<()>,
+    {
+        let conversion_ms = conversion_period_ms(Conversion::Ms15_5, average) as u64;
+        let mut last = None;
+        loop {
+            let now = clock.now();
+            let mut next = (now / 1000 + 1) * 1000;
+            // A conversion ready slightly early must not sample the same second twice
+            if let Some(last) = last {
+                next = next.max(last + 1000);
+            }
+            let start = next.saturating_sub(conversion_ms);
+            if start > now {
+                delay.delay_ms((start - now) as u32);
+            }
+
+            let value = self.oneshot(average)?;
+            last = Some(next);
+            if on_sample(Timestamped { value, at: next }).is_break() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Sample periodically with oneshots, which uses less power than the continuous mode.
+    /// Every `period_ms`, a oneshot is done and `on_sample` is called with the temperature in celsius
+    /// until it returns [ControlFlow::Break]. The delay waits for the remainder of the period after the conversion
+    pub fn sample_periodic<D, F>(
+        &mut self,
+        delay: &mut D,
+        period_ms: u32,
+        average: Average,
+        mut on_sample: F,
+    ) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+        F: FnMut(f32) -> ControlFlow<()>,
+    {
+        let conversion_ms = conversion_period_ms(Conversion::Ms15_5, average);
+        while let ControlFlow::Continue(()) = on_sample(self.oneshot(average)?) {
+            delay.delay_ms(period_ms.saturating_sub(conversion_ms));
+        }
+        Ok(())
+    }
+
+    /// Read the temperature of both devices with a oneshot and return the difference in celsius,
+    /// positive if this device is warmer than the other
+    pub fn read_delta<T2>(
+        &mut self,
+        other: &mut Tmp117<T2, E>,
+        average: Average,
+    ) -> Result<Celsius, Error<E>>
+    where
+        T2: I2c<SevenBitAddress, Error = E>,
+    {
+        let temp = self.oneshot(average)?;
+        let other_temp = other.oneshot(average)?;
+        Ok(Celsius(temp).delta(Celsius(other_temp)))
+    }
+
+    /// Pass a config and closure for the continuous mode.
+    /// The device gets set to continuous, then the function is called with the handler
+    /// and finally the device is shutdown
+    pub fn continuous<F>(&mut self, config: ContinuousConfig, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(ContinuousHandler<'_, T, E>) -> Result<(), Error<E>>,
+    {
+        let handler = self.set_continuous(config)?;
+        f(handler)?;
+        self.set_shutdown()
+    }
+
+    /// Use a device already running in continuous mode, for example after [Tmp117::adopt], without reconfiguring it.
+    /// Returns [Error::UnexpectedMode] if the device is not in continuous mode.
+    /// The device is left running after the closure
+    pub fn resume_continuous<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(ContinuousHandler<'_, T, E>) -> Result<(), Error<E>>,
+    {
+        let config: Configuration = self.tmp_ll.read()?;
+        if config.mode() != ConversionMode::Continuous {
+            return Err(Error::UnexpectedMode);
+        }
+        f(ContinuousHandler { tmp117: self })
+    }
+
+    /// Sample in continuous mode, calling `on_sample` with each temperature in celsius
+    /// until it returns [ControlFlow::Break]. The device is then shutdown.
+    /// Each sample waits for the data ready flag, so [Error::DataNotReady] never ends the loop, only bus errors do
+    pub fn run_continuous<F>(
+        &mut self,
+        config: ContinuousConfig,
+        mut on_sample: F,
+    ) -> Result<(), Error<E>>
+    where
+        F: FnMut(f32) -> ControlFlow<()>,
+    {
+        self.continuous(config, |mut t| {
+            while let ControlFlow::Continue(()) = on_sample(t.wait_temp()?) {}
+            Ok(())
+        })
+    }
+}
+
+/// Handler for the continuous mode
 pub struct ContinuousHandler<'a, T, E> {
     tmp117: &'a mut Tmp117<T, E>,
 }
@@ -263,8 +1485,138 @@ where
             return Err(Error::DataNotReady);
         }
 
-        let val = self.tmp117.read_temp_raw()?;
-        Ok(val)
+        let val = self.tmp117.read_temp_raw()?;
+        Ok(val)
+    }
+
+    /// Read the temperature in celsius and the alert flags from the same configuration read used to check the data ready flag,
+    /// saving a transaction compared to [get_alert](Self::get_alert). Return an error if the value of the temperature is not ready
+    pub fn read_temp_alert_aware(&mut self) -> Result<(f32, Alert), Error<E>> {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        if !config.data_ready() {
+            return Err(Error::DataNotReady);
+        }
+
+        let val = self.tmp117.read_temp_raw()?;
+        Ok((val, Alert::from_config(&config)))
+    }
+
+    /// Same as [read_temp_alert_aware](Self::read_temp_alert_aware), but returns a [Measurement] with the raw temperature
+    pub fn read_measurement(&mut self) -> Result<Measurement, Error<E>> {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        if !config.data_ready() {
+            return Err(Error::DataNotReady);
+        }
+
+        let raw = self.tmp117.tmp_ll.read_temperature_raw()?;
+        Ok(Measurement {
+            raw,
+            alert: Alert::from_config(&config),
+        })
+    }
+
+    /// Same as [read_temp](Self::read_temp), but reads again up to `retries` times on [Error::InvalidData],
+    /// since a corrupted read is often transient. Note that the corrupted read may have cleared the data ready flag,
+    /// in which case [Error::DataNotReady] is returned
+    pub fn read_temp_resilient(&mut self, retries: u8) -> Result<f32, Error<E>> {
+        let mut attempts = 0;
+        loop {
+            match self.read_temp() {
+                Err(Error::InvalidData) if attempts < retries => attempts += 1,
+                res => return res,
+            }
+        }
+    }
+
+    /// Read the temperature in celsius and timestamp it with the clock,
+    /// return an error if the value of the temperature is not ready
+    #[cfg(feature = "clock")]
+    pub fn read_temp_at<C>(&mut self, clock: &C) -> Result<Timestamped<f32, C::Instant>, Error<E>>
+    where
+        C: Clock,
+    {
+        let value = self.read_temp()?;
+        Ok(Timestamped {
+            value,
+            at: clock.now(),
+        })
+    }
+
+    /// Returns true if a new temperature is ready. Note that reading the flag clears it,
+    /// use [read_latest](Self::read_latest) to then read the temperature without checking it again
+    pub fn is_data_ready(&mut self) -> Result<bool, Error<E>> {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        Ok(config.data_ready())
+    }
+
+    /// Poll the data ready flag, for use with [core::future::poll_fn] in executors without an async i2c bus.
+    /// Each poll does a blocking i2c read, and the task is woken right away when the data is not ready,
+    /// so the executor busy-spins on the bus until the data is ready instead of sleeping.
+    /// To sleep until the data is ready, use the [asynchronous] driver with the alert pin.
+    /// Reading the flag clears it, use [read_latest](Self::read_latest) once ready
+    pub fn poll_data_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error<E>>> {
+        match self.is_data_ready() {
+            Ok(true) => Poll::Ready(Ok(())),
+            Ok(false) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+
+    /// Read the most recent temperature in celsius without checking the data ready flag.
+    /// Useful for consumers reading less often than the conversion cycle, since the flag may already be cleared.
+    /// Since the configuration is not read, the alert flags are not cleared and stay available to [get_alert](Self::get_alert)
+    pub fn read_latest(&mut self) -> Result<f32, Error<E>> {
+        self.tmp117.read_temp_raw()
+    }
+
+    /// Read the temperature in mK, return an error if the value of the temperature is not ready.
+    /// See [raw_to_millikelvin] for the conversion
+    pub fn read_temp_millikelvin(&mut self) -> Result<i32, Error<E>> {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        if !config.data_ready() {
+            return Err(Error::DataNotReady);
+        }
+
+        let temp: Temperature = self.tmp117.tmp_ll.read()?;
+        Ok(raw_to_millikelvin(temp.into()))
+    }
+
+    /// Trigger a conversion now with a oneshot, wait for it and read the temperature in celsius.
+    /// The delay waits for the nominal conversion time before polling the data ready flag.
+    /// The device is then put back in continuous mode, which restarts its conversion cycle
+    pub fn force_conversion<D>(&mut self, delay: &mut D) -> Result<f32, Error<E>>
+    where
+        D: DelayNs,
+    {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        self.tmp117
+            .tmp_ll
+            .edit(|r: &mut Configuration| r.set_mode(ConversionMode::OneShot))?;
+        delay.delay_ms(conversion_period_ms(Conversion::Ms15_5, config.average()));
+        self.tmp117.wait_for_data()?;
+        let temp = self.tmp117.read_temp_raw()?;
+        self.tmp117
+            .tmp_ll
+            .edit(|r: &mut Configuration| r.set_mode(ConversionMode::Continuous))?;
+        Ok(temp)
+    }
+
+    /// Wait for and discard `n` readings, to let the averaging settle and skip the first conversion after a mode change.
+    /// The delay waits for the conversion period before polling for each reading
+    pub fn warmup<D>(&mut self, n: u8, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        for _ in 0..n {
+            delay.delay_ms(period);
+            self.wait_temp()?;
+        }
+        Ok(())
     }
 
     /// Wait for the data to be ready and read the temperature in celsius
@@ -274,7 +1626,28 @@ where
         Ok(val)
     }
 
-    /// Check if an alert was triggered since the last calll
+    /// Same as [read_temp](Self::read_temp), but first waits the interval of the delay if a previous read with it succeeded
+    pub fn read_temp_delayed<D>(&mut self, delay: &mut ReadDelay<D>) -> Result<f32, Error<E>>
+    where
+        D: DelayNs,
+    {
+        if delay.pending {
+            delay.delay.delay_us(delay.interval_us);
+        }
+        let val = self.read_temp()?;
+        delay.pending = true;
+        Ok(val)
+    }
+
+    /// Wait for the data to be ready and return the temperature in celsius passed through the filter
+    pub fn read_temp_filtered(&mut self, filter: &mut IirFilter) -> Result<Celsius, Error<E>> {
+        let val = self.wait_temp()?;
+        Ok(filter.update(Celsius(val)))
+    }
+
+    /// Check if an alert was triggered since the last call. Does a single i2c transaction reading the configuration,
+    /// which latches and clears the alert flags. To handle the alert pin in an interrupt,
+    /// set a flag in the handler and call this from a task since the bus cannot be used in the interrupt
     pub fn get_alert(&mut self) -> Result<Alert, Error<E>> {
         let val = self.tmp117.check_alert()?;
         Ok(val)
@@ -285,4 +1658,1244 @@ where
         let val = self.tmp117.wait_for_alert()?;
         Ok(val)
     }
+
+    /// Wait for the data to be ready and push the temperature in celsius in the queue.
+    /// Returns false if the queue was full and the value was dropped
+    #[cfg(feature = "heapless")]
+    pub fn read_into_queue<const N: usize>(
+        &mut self,
+        producer: &mut heapless::spsc::Producer<'_, Celsius, N>,
+    ) -> Result<bool, Error<E>> {
+        let val = self.wait_temp()?;
+        Ok(producer.enqueue(Celsius(val)).is_ok())
+    }
+
+    /// Read the temperature twice, one conversion period apart, and return the second one in celsius.
+    /// Returns [Error::StuckSensor] if both raw values are identical, which is unlikely for a live sensor given the noise on the last bits.
+    /// A very stable environment with a lot of averaging can still give a false positive, retry before considering the sensor dead
+    pub fn read_temp_liveness<D>(&mut self, delay: &mut D) -> Result<Celsius, Error<E>>
+    where
+        D: DelayNs,
+    {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        self.tmp117.wait_for_data()?;
+        let first = self.tmp117.tmp_ll.read_temperature_raw()?;
+        delay.delay_ms(period + 1);
+        self.tmp117.wait_for_data()?;
+        let second = self.tmp117.tmp_ll.read_temperature_raw()?;
+        if first == second {
+            return Err(Error::StuckSensor);
+        }
+        Ok(Celsius(decode_temperature(second as u16)))
+    }
+
+    /// Wait for the data and return whether a heating output should be active, for a thermostat without the alert pin.
+    /// Turns on below `setpoint - hysteresis` and off above `setpoint + hysteresis`, otherwise keeps the `prev` state
+    pub fn control_state(
+        &mut self,
+        setpoint: Celsius,
+        hysteresis: Celsius,
+        prev: bool,
+    ) -> Result<bool, Error<E>> {
+        let temp = self.wait_temp()?;
+        Ok(heat_on(temp, setpoint, hysteresis, prev))
+    }
+
+    /// Average `n` consecutive temperatures in software on top of the hardware averaging, for a single low noise reading.
+    /// The delay waits for the conversion period before polling for each one, the mean is computed in m°C with integer math.
+    /// At least one temperature is read
+    pub fn measure_stable<D>(&mut self, n: u8, delay: &mut D) -> Result<Celsius, Error<E>>
+    where
+        D: DelayNs,
+    {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        let n = n.max(1);
+        let mut sum: i64 = 0;
+        for _ in 0..n {
+            delay.delay_ms(period);
+            self.tmp117.wait_for_data()?;
+            let raw = self.tmp117.tmp_ll.read_temperature_raw()?;
+            sum += raw_to_millicelsius(raw as u16) as i64;
+        }
+        Ok(Celsius((sum / n as i64) as f32 / 1000.0))
+    }
+
+    /// Collect `N` consecutive temperatures, the delay waits for the conversion period before polling for each one.
+    /// Stops at the first error
+    #[cfg(feature = "heapless")]
+    pub fn read_burst<D, const N: usize>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<heapless::Vec<Celsius, N>, Error<E>>
+    where
+        D: DelayNs,
+    {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        let period = conversion_period_ms(config.conversion(), config.average());
+        let mut samples = heapless::Vec::new();
+        while !samples.is_full() {
+            delay.delay_ms(period);
+            let _ = samples.push(Celsius(self.wait_temp()?));
+        }
+        Ok(samples)
+    }
+
+    /// Wait for the data to be ready and read the temperature as a uom quantity
+    #[cfg(feature = "uom")]
+    pub fn read_temp_uom(&mut self) -> Result<uom::si::f32::ThermodynamicTemperature, Error<E>> {
+        let val = self.wait_temp()?;
+        Ok(uom::si::f32::ThermodynamicTemperature::new::<
+            uom::si::thermodynamic_temperature::degree_celsius,
+        >(val))
+    }
+
+    /// Wait for the next event. Depending on the current [AlertPinSelect],
+    /// either waits for the data and returns the temperature or waits for an alert
+    pub fn wait_event(&mut self) -> Result<Event, Error<E>> {
+        let config: Configuration = self.tmp117.tmp_ll.read()?;
+        match config.dr_alert() {
+            AlertPinSelect::DataReady => {
+                if !config.data_ready() {
+                    self.tmp117.wait_for_data()?;
+                }
+                let val = self.tmp117.read_temp_raw()?;
+                Ok(Event::DataReady(Celsius(val)))
+            }
+            AlertPinSelect::Alert => match Alert::from_config(&config) {
+                Alert::None => Ok(Event::Alert(self.tmp117.wait_for_alert()?)),
+                alert => Ok(Event::Alert(alert)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{future::poll_fn, pin::pin};
+    use std::format;
+
+    use super::*;
+    use crate::test_util::{poll_once, MockDelay, RecordingI2c, Transaction};
+
+    type Bus = RecordingI2c<256>;
+
+    #[test]
+    fn oneshot_returns_to_shutdown() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.oneshot(Average::NoAverage), Ok(25.0));
+        assert_eq!(bus.registers[1] & 0x0C00, 0x0400);
+    }
+
+    #[test]
+    fn oneshot_stuck_in_oneshot() {
+        let mut bus = Bus::new();
+        // Configuration read by the edit, the data ready poll and the mode check
+        bus.queue_read(0x01, 0x0220);
+        bus.queue_read(0x01, 0x2400);
+        bus.queue_read(0x01, 0x0C00);
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.oneshot(Average::NoAverage), Err(Error::UnexpectedMode));
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn read_into_queue_fills_up() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut queue = heapless::spsc::Queue::<Celsius, 3>::new();
+        let (mut producer, mut consumer) = queue.split();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.continuous(Default::default(), |mut t| {
+            assert!(t.read_into_queue(&mut producer)?);
+            assert!(t.read_into_queue(&mut producer)?);
+            assert!(!t.read_into_queue(&mut producer)?);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(consumer.dequeue(), Some(Celsius(25.0)));
+        assert_eq!(consumer.dequeue(), Some(Celsius(25.0)));
+        assert_eq!(consumer.dequeue(), None);
+    }
+
+    #[test]
+    fn adopt_each_mode() {
+        for (raw, mode) in [
+            (0x0220, ConversionMode::Continuous),
+            (0x0620, ConversionMode::Shutdown),
+            (0x0E20, ConversionMode::OneShot),
+        ] {
+            let mut bus = Bus::new();
+            bus.registers[1] = raw;
+            bus.conversion_polls = u8::MAX;
+            let (_, adopted) = Tmp117::adopt(&mut bus, 0x48).unwrap();
+            assert_eq!(adopted, mode);
+            assert_eq!(bus.writes(0x01), 0);
+        }
+    }
+
+    #[test]
+    fn time_to_data_ready_configs() {
+        let mut bus = Bus::new();
+        bus.conversion_polls = u8::MAX;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.time_to_data_ready(), Ok(1000));
+
+        // A oneshot takes the time of the averaging only
+        tmp.write_config_raw(0x0440).unwrap();
+        assert_eq!(tmp.time_to_data_ready(), Ok(500));
+
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.time_to_data_ready(), Ok(0));
+    }
+
+    #[test]
+    fn alert_pin_state_polarities() {
+        assert_eq!(
+            Alert::None.as_pin_state(Polarity::ActiveLow),
+            PinState::High
+        );
+        assert_eq!(Alert::High.as_pin_state(Polarity::ActiveLow), PinState::Low);
+        assert_eq!(
+            Alert::HighLow.as_pin_state(Polarity::ActiveLow),
+            PinState::Low
+        );
+        assert_eq!(
+            Alert::None.as_pin_state(Polarity::ActiveHigh),
+            PinState::Low
+        );
+        assert_eq!(
+            Alert::Low.as_pin_state(Polarity::ActiveHigh),
+            PinState::High
+        );
+    }
+
+    #[test]
+    fn read_latest_after_data_ready_cleared() {
+        let mut bus = Bus::new();
+        bus.registers[0] = 0x0C80;
+        bus.conversion_polls = u8::MAX;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.continuous(Default::default(), |mut t| {
+            assert_eq!(t.read_temp(), Err(Error::DataNotReady));
+            assert_eq!(t.read_latest(), Ok(25.0));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn new_checked_clone_id() {
+        let mut bus = Bus::new();
+        bus.registers[0x0F] = 0x1118;
+        let id = Id {
+            device: 0x118,
+            revision: 1,
+        };
+        assert_eq!(
+            Tmp117::new_checked(&mut bus, 0x48, false).err(),
+            Some(Error::UnexpectedDevice(id))
+        );
+        let (_, accepted) = Tmp117::new_checked(&mut bus, 0x48, true).unwrap();
+        assert_eq!(accepted, id);
+    }
+
+    #[test]
+    fn config_raw_round_trip() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0xE684;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.read_config_raw(), Ok(0xE684));
+
+        // The read only flags are not written
+        tmp.write_config_raw(0xF6A4).unwrap();
+        assert_eq!(tmp.read_config_raw(), Ok(0x06A4));
+    }
+
+    #[test]
+    fn warn_if_too_fast_config() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.warn_if_too_fast(2000), Err(Error::SampleRateTooHigh));
+        assert_eq!(tmp.warn_if_too_fast(1000), Ok(()));
+    }
+
+    #[test]
+    fn raw_limits_negative() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.set_high_limit_raw(-1280).unwrap();
+        tmp.set_low_limit_raw(i16::MIN).unwrap();
+        assert_eq!(tmp.high_limit_raw(), Ok(-1280));
+        assert_eq!(tmp.low_limit_raw(), Ok(i16::MIN));
+        assert_eq!(bus.registers[2], 0xFB00);
+        assert_eq!(bus.registers[3], 0x8000);
+    }
+
+    #[test]
+    fn decode_temperature_matches_driver() {
+        for raw in [0x0C80, 0xEC00, 0x0001, 0xFFFF, 0x7FFF, 0x8000] {
+            let mut bus = Bus::new();
+            bus.temperature = raw;
+            let mut tmp = Tmp117::new(&mut bus, 0x48);
+            tmp.continuous(Default::default(), |mut t| {
+                assert_eq!(t.read_temp(), Ok(decode_temperature(raw)));
+                Ok(())
+            })
+            .unwrap();
+        }
+        assert_eq!(decode_temperature(0xEC00), -40.0);
+    }
+
+    #[test]
+    fn id_parsed_and_displayed() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let id = tmp.id().unwrap();
+        assert_eq!(
+            id,
+            Id {
+                device: DEVICE_ID,
+                revision: 1
+            }
+        );
+        assert_eq!(format!("{}", id), "TMP117 rev 1");
+
+        let other = Id {
+            device: 0x118,
+            revision: 2,
+        };
+        assert_eq!(format!("{}", other), "device 0x118 rev 2");
+    }
+
+    #[test]
+    fn run_continuous_breaks_after_three() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut samples = 0;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.run_continuous(Default::default(), |temp| {
+            assert_eq!(temp, 25.0);
+            samples += 1;
+            if samples == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+        assert_eq!(samples, 3);
+        assert_eq!(bus.reads(0x00), 3);
+        assert_eq!(bus.registers[1] & 0x0C00, 0x0400);
+    }
+
+    #[test]
+    fn eeprom_busy_both_flags() {
+        let mut bus = Bus::new();
+        bus.registers[4] = 0x8000;
+        bus.program_polls = 1;
+        let mut ll = Tmp117LL::new(&mut bus, 0x48);
+        ll.write(UEEPROM2::from(0x1234)).unwrap();
+        let eeprom: EEPROM = ll.read().unwrap();
+        assert!(eeprom.busy());
+
+        let mut tmp = Tmp117::new_from_ll(ll);
+        assert_eq!(tmp.eeprom_busy(), Ok(true));
+        assert_eq!(tmp.eeprom_busy(), Ok(false));
+    }
+
+    #[test]
+    fn read_delta_two_sensors() {
+        let mut warm = Bus::new();
+        warm.temperature = 0x0C80;
+        let mut cold = Bus::new();
+        cold.temperature = 0x0A00;
+        let mut a = Tmp117::new(&mut warm, 0x48);
+        let mut b = Tmp117::new(&mut cold, 0x49);
+        assert_eq!(a.read_delta(&mut b, Average::NoAverage), Ok(Celsius(5.0)));
+        assert_eq!(b.read_delta(&mut a, Average::NoAverage), Ok(Celsius(-5.0)));
+        assert_eq!(Celsius(25.0).delta(Celsius(20.0)), Celsius(5.0));
+        assert_eq!(Celsius(-10.0).delta(Celsius(20.0)), Celsius(-30.0));
+    }
+
+    #[test]
+    fn read_temp_resilient_after_corrupt_read() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        // Read by resume_continuous, then two reads with an invalid conversion mode
+        bus.queue_read(0x01, 0x0220);
+        bus.queue_read(0x01, 0x2800);
+        bus.queue_read(0x01, 0x2800);
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.resume_continuous(|mut t| {
+            assert_eq!(t.read_temp_resilient(0), Err(Error::InvalidData));
+            assert_eq!(t.read_temp_resilient(1), Ok(25.0));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn thermal_with_data_ready_pin() {
+        let mut bus = Bus::new();
+        let config = ContinuousConfig {
+            trigger_mode: TriggerMode::Thermal,
+            pin_select: Some(AlertPinSelect::DataReady),
+            ..Default::default()
+        };
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.continuous(config, |_| Ok(())).unwrap();
+        let config = Configuration::try_from(bus.registers[1]).unwrap();
+        assert_eq!(config.trigger_mode(), TriggerMode::Thermal);
+        assert_eq!(config.dr_alert(), AlertPinSelect::DataReady);
+    }
+
+    #[test]
+    fn skip_estimator_periods() {
+        let skip = SkipEstimator::new(Conversion::Ms1000, Average::Avg8);
+        assert_eq!(skip.skipped(999), 0);
+        assert_eq!(skip.skipped(1000), 0);
+        assert_eq!(skip.skipped(2500), 1);
+        assert_eq!(skip.skipped(5000), 4);
+
+        let skip = SkipEstimator::new(Conversion::Ms125, Average::Avg32);
+        assert_eq!(skip.skipped(2000), 3);
+    }
+
+    #[test]
+    fn read_temp_alert_aware_with_alert() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x6400;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.continuous(Default::default(), |mut t| {
+            assert_eq!(t.read_temp_alert_aware(), Ok((200.0, Alert::High)));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn oneshot_with_writes_both_fields() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.oneshot_with(Average::Avg32, Conversion::Ms4000)
+            .unwrap();
+        let written = bus.transactions().iter().find_map(|t| match t {
+            Transaction::Write {
+                register: 0x01,
+                value,
+                ..
+            } => Some(Configuration::try_from(*value).unwrap()),
+            _ => None,
+        });
+        let written = written.unwrap();
+        assert_eq!(written.mode(), ConversionMode::OneShot);
+        assert_eq!(written.average(), Average::Avg32);
+        assert_eq!(written.conversion(), Conversion::Ms4000);
+    }
+
+    #[test]
+    fn is_data_ready_states() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        // Every other read of the configuration sees a new conversion
+        bus.conversion_polls = 1;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.resume_continuous(|mut t| {
+            assert_eq!(t.is_data_ready(), Ok(true));
+            assert_eq!(t.read_latest(), Ok(25.0));
+            assert_eq!(t.is_data_ready(), Ok(false));
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn write_user_words_keeps_ueeprom1() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0400;
+        bus.registers[5] = 0x5A5A;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.write_user_words([7, 8], &mut MockDelay::new()).unwrap();
+        assert_eq!(bus.writes(0x05), 0);
+        assert_eq!(bus.registers[5..8], [0x5A5A, 7, 8]);
+    }
+
+    #[test]
+    fn sample_periodic_cycles() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut delay = MockDelay::new();
+        let mut samples = 0;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.sample_periodic(&mut delay, 1000, Average::NoAverage, |temp| {
+            assert_eq!(temp, 25.0);
+            samples += 1;
+            if samples == 4 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+
+        // A oneshot per cycle, the delay waits for the period minus the 16ms conversion
+        assert_eq!(bus.writes(0x01), 4);
+        assert_eq!(delay.calls, 3);
+        assert_eq!(delay.ns, 3 * 984_000_000);
+    }
+
+    #[test]
+    fn recover_corrupt_config() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0E96;
+        bus.registers[2] = 0x8000;
+        let config = ContinuousConfig {
+            average: Average::Avg64,
+            conversion: Conversion::Ms4000,
+            high: Some(50.0),
+            ..Default::default()
+        };
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let id = tmp.recover(&mut MockDelay::new(), config).unwrap();
+        assert_eq!(id.device, DEVICE_ID);
+
+        let recovered = Configuration::try_from(bus.registers[1]).unwrap();
+        assert_eq!(recovered.mode(), ConversionMode::Shutdown);
+        assert_eq!(recovered.average(), Average::Avg64);
+        assert_eq!(recovered.conversion(), Conversion::Ms4000);
+        assert_eq!(recovered.trigger_mode(), TriggerMode::Alert);
+        assert_eq!(bus.registers[2], 0x1900);
+    }
+
+    #[test]
+    fn probe_outcomes() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.probe().unwrap().map(|id| id.device), Some(DEVICE_ID));
+
+        let nack = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+        bus.fail_next(nack);
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.probe(), Ok(None));
+
+        bus.fail_next(ErrorKind::ArbitrationLoss);
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.probe(), Err(Error::Bus(ErrorKind::ArbitrationLoss)));
+    }
+
+    #[test]
+    fn snapshot_negative_low_limit() {
+        let mut bus = Bus::new();
+        bus.registers[3] = 0xFB00;
+        bus.registers[8] = 0xFF80;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let snapshot = tmp.snapshot().unwrap();
+        assert_eq!(snapshot.high_limit, Celsius(192.0));
+        assert_eq!(snapshot.low_limit, Celsius(-10.0));
+        assert_eq!(snapshot.offset, Celsius(-1.0));
+    }
+
+    #[cfg(feature = "clock")]
+    struct FakeClock(core::cell::Cell<u64>);
+
+    #[cfg(feature = "clock")]
+    impl Clock for FakeClock {
+        type Instant = u64;
+
+        fn now(&self) -> u64 {
+            let now = self.0.get();
+            self.0.set(now + 10);
+            now
+        }
+    }
+
+    #[cfg(feature = "clock")]
+    #[test]
+    fn read_temp_at_fake_clock() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let clock = FakeClock(core::cell::Cell::new(1234));
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.continuous(Default::default(), |mut t| {
+            let first = t.read_temp_at(&clock)?;
+            let second = t.read_temp_at(&clock)?;
+            assert_eq!(
+                first,
+                Timestamped {
+                    value: 25.0,
+                    at: 1234
+                }
+            );
+            assert_eq!(second.at, 1244);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    /// Clock in milliseconds advanced by the delays only
+    #[cfg(feature = "clock")]
+    struct SimClock(core::cell::Cell<u64>);
+
+    #[cfg(feature = "clock")]
+    impl Clock for SimClock {
+        type Instant = u64;
+
+        fn now(&self) -> u64 {
+            self.0.get() / 1_000_000
+        }
+    }
+
+    #[cfg(feature = "clock")]
+    impl DelayNs for &SimClock {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.set(self.0.get() + ns as u64);
+        }
+    }
+
+    #[cfg(feature = "clock")]
+    #[test]
+    fn sample_aligned_fake_clock() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0400;
+        bus.temperature = 0x0C80;
+        let clock = SimClock(core::cell::Cell::new(1_234_000_000));
+        let mut delay = &clock;
+        let mut samples = heapless::Vec::<(u64, u64), 3>::new();
+        Tmp117::new(&mut bus, 0x48)
+            .sample_aligned(&clock, &mut delay, Average::NoAverage, |sample| {
+                assert_eq!(sample.value, 25.0);
+                let _ = samples.push((sample.at, clock.now()));
+                if samples.is_full() {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        for (i, (at, read)) in samples.iter().enumerate() {
+            assert_eq!(*at, 2000 + 1000 * i as u64);
+            // The conversion of 15.5 ms is started early so the data is ready at the boundary
+            assert!((at - 16..=*at).contains(read));
+        }
+    }
+
+    #[test]
+    fn blocked_while_eeprom_unlocked() {
+        let mut bus = Bus::new();
+        bus.registers[4] = 0x8000;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let unlocked = Err(Error::EepromUnlocked);
+        assert_eq!(tmp.continuous(Default::default(), |_| Ok(())), unlocked);
+        assert_eq!(tmp.oneshot(Average::NoAverage).map(|_| ()), unlocked);
+        assert_eq!(
+            tmp.recover(&mut MockDelay::new(), Default::default())
+                .map(|_| ()),
+            unlocked
+        );
+        assert_eq!(tmp.set_high_limit_raw(0), unlocked);
+        assert!(!bus
+            .transactions()
+            .iter()
+            .any(|t| matches!(t, Transaction::Write { .. })));
+
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.allow_eeprom_programming(true);
+        assert!(tmp.oneshot(Average::NoAverage).is_ok());
+    }
+
+    #[test]
+    fn resume_continuous_modes() {
+        let mut bus = Bus::new();
+        let (mut tmp, mode) = Tmp117::adopt(&mut bus, 0x48).unwrap();
+        assert_eq!(mode, ConversionMode::Continuous);
+        let mut called = false;
+        tmp.resume_continuous(|_| {
+            called = true;
+            Ok(())
+        })
+        .unwrap();
+        assert!(called);
+        // Left running and never reconfigured
+        assert_eq!(bus.writes(0x01), 0);
+        assert_eq!(bus.registers[1] & 0x0C00, 0);
+
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0620;
+        let (mut tmp, _) = Tmp117::adopt(&mut bus, 0x48).unwrap();
+        assert_eq!(
+            tmp.resume_continuous(|_| panic!("not in continuous")),
+            Err(Error::UnexpectedMode)
+        );
+    }
+
+    #[test]
+    fn force_conversion_restores_continuous() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        // The oneshot is not done right after the nominal delay
+        bus.conversion_polls = 2;
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.resume_continuous(|mut t| {
+            assert_eq!(t.force_conversion(&mut delay), Ok(25.0));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(bus.registers[1] & 0x0C00, 0);
+        assert_eq!(delay.ns, 125_000_000);
+    }
+
+    #[test]
+    fn warmup_discards_readings() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        bus.conversion_polls = 1;
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.resume_continuous(|mut t| {
+            t.warmup(3, &mut delay)?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(bus.reads(0x00), 3);
+        assert_eq!(delay.calls, 3);
+    }
+
+    #[test]
+    fn export_import_between_sensors() {
+        let mut golden = Bus::new();
+        golden.registers[1] = 0x0494;
+        golden.registers[2] = 0x1900;
+        golden.registers[3] = 0xF380;
+        golden.registers[5..8].copy_from_slice(&[1, 2, 3]);
+        golden.registers[8] = 0x0010;
+        let config = Tmp117::new(&mut golden, 0x48).export_config(true).unwrap();
+
+        let mut target = Bus::new();
+        target.registers[1] = 0x0400;
+        let mut delay = MockDelay::new();
+        Tmp117::new(&mut target, 0x48)
+            .import_config(&config, &mut delay)
+            .unwrap();
+        assert_eq!(target.registers[1] & 0x0FFE, 0x0494);
+        assert_eq!(target.registers[2..4], [0x1900, 0xF380]);
+        assert_eq!(target.registers[5..9], [1, 2, 3, 0x0010]);
+    }
+
+    #[test]
+    fn poll_data_ready_manually() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        bus.conversion_polls = 2;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let mut polls = 0;
+        tmp.resume_continuous(|mut t| {
+            {
+                let mut ready = pin!(poll_fn(|cx| t.poll_data_ready(cx)));
+                loop {
+                    polls += 1;
+                    if let Poll::Ready(res) = poll_once(ready.as_mut()) {
+                        res?;
+                        break;
+                    }
+                }
+            }
+            assert_eq!(t.read_latest(), Ok(25.0));
+            Ok(())
+        })
+        .unwrap();
+        // The configuration read of resume_continuous is the first of the reads without data
+        assert_eq!(polls, 2);
+    }
+
+    #[test]
+    fn wait_eeprom_polls_busy_window() {
+        let mut bus = Bus::new();
+        for _ in 0..3 {
+            bus.queue_read(0x01, 0x1400);
+        }
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.wait_eeprom(&mut delay), Ok(()));
+        assert_eq!(bus.reads(0x01), 4);
+        assert_eq!(delay.calls, 3);
+        assert_eq!(delay.ns, 3 * EEPROM_POLL_US as u64 * 1000);
+
+        // Stays busy
+        bus.clear();
+        bus.registers[1] |= 0x1000;
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(tmp.wait_eeprom(&mut delay), Err(Error::EepromTimeout));
+        let polls = EEPROM_TIMEOUT_US / EEPROM_POLL_US;
+        assert_eq!(bus.reads(0x01), polls as usize);
+        assert_eq!(delay.ns, (polls * EEPROM_POLL_US) as u64 * 1000);
+    }
+
+    #[cfg(feature = "uom")]
+    #[test]
+    fn read_temp_uom_matches_celsius() {
+        use uom::si::thermodynamic_temperature::{degree_celsius, kelvin};
+
+        let mut bus = Bus::new();
+        bus.temperature = 0xFB00;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.resume_continuous(|mut t| {
+            let celsius = t.wait_temp()?;
+            let temp = t.read_temp_uom()?;
+            assert_eq!(celsius, -10.0);
+            assert_eq!(temp.get::<degree_celsius>(), celsius);
+            assert!((temp.get::<kelvin>() - 263.15).abs() < 1e-3);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn read_burst_eight_samples() {
+        let mut bus = Bus::new();
+        for i in 0..8 {
+            bus.queue_read(0x00, 0x0C80 + i * 0x80);
+        }
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let mut samples = heapless::Vec::new();
+        tmp.resume_continuous(|mut t| {
+            samples = t.read_burst::<_, 8>(&mut delay)?;
+            Ok(())
+        })
+        .unwrap();
+        let expected: heapless::Vec<Celsius, 8> =
+            (0..8).map(|i| Celsius(25.0 + i as f32)).collect();
+        assert_eq!(samples, expected);
+        assert_eq!(delay.calls, 8);
+        assert_eq!(bus.reads(0x00), 8);
+    }
+
+    #[test]
+    fn get_alert_single_transaction() {
+        let mut bus = Bus::new();
+        Tmp117::new(&mut bus, 0x48)
+            .resume_continuous(|_| Ok(()))
+            .unwrap();
+        let baseline = bus.transactions().len();
+
+        let mut bus = Bus::new();
+        bus.temperature = 0x6400;
+        let mut alert = Alert::None;
+        Tmp117::new(&mut bus, 0x48)
+            .resume_continuous(|mut t| {
+                alert = t.get_alert()?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(alert, Alert::High);
+        assert_eq!(bus.transactions().len(), baseline + 1);
+        assert_eq!(bus.reads(0x01), 2);
+    }
+
+    #[test]
+    fn alert_u8_round_trip() {
+        for (alert, bits) in [
+            (Alert::None, 0),
+            (Alert::Low, 1),
+            (Alert::High, 2),
+            (Alert::HighLow, 3),
+        ] {
+            assert_eq!(u8::from(alert), bits);
+            assert_eq!(Alert::try_from(bits), Ok(alert));
+        }
+        assert_eq!(Alert::try_from(4), Err(4));
+    }
+
+    #[test]
+    fn measure_stable_noisy_sequence() {
+        let mut bus = Bus::new();
+        // 25.0, 25.5, 24.5 and 25.25 °C
+        for raw in [0x0C80, 0x0CC0, 0x0C40, 0x0CA0] {
+            bus.queue_read(0x00, raw);
+        }
+        let mut delay = MockDelay::new();
+        let mut mean = Celsius(0.0);
+        Tmp117::new(&mut bus, 0x48)
+            .resume_continuous(|mut t| {
+                mean = t.measure_stable(4, &mut delay)?;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(mean, Celsius(25.062));
+        assert_eq!(bus.reads(0x00), 4);
+        assert_eq!(delay.calls, 4);
+    }
+
+    #[test]
+    fn wait_ready_after_reset_skips_sentinel() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        bus.program_polls = 2;
+        // No conversion completes before the device is put in shutdown
+        bus.conversion_polls = 1;
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.reset(&mut delay).unwrap();
+        // Reading right after the reset returns the power up value
+        assert_eq!(tmp.read_temp_raw(), Ok(-256.0));
+        assert_eq!(tmp.wait_ready_after_reset(&mut delay), Ok(25.0));
+        assert_eq!(bus.registers[1] & 0x0C00, 0x0400);
+    }
+
+    #[test]
+    fn measurement_bytes_round_trip() {
+        for (measurement, bytes) in [
+            (
+                Measurement {
+                    raw: -1280,
+                    alert: Alert::HighLow,
+                },
+                [0xFB, 0x00, 3, 0],
+            ),
+            (
+                Measurement {
+                    raw: i16::MIN,
+                    alert: Alert::Low,
+                },
+                [0x80, 0x00, 1, 0],
+            ),
+            (
+                Measurement {
+                    raw: 0x0C80,
+                    alert: Alert::None,
+                },
+                [0x0C, 0x80, 0, 0],
+            ),
+        ] {
+            assert_eq!(measurement.to_bytes(), bytes);
+            assert_eq!(Measurement::from_bytes(bytes), Some(measurement));
+        }
+        assert_eq!(Measurement::from_bytes([0xFB, 0x00, 4, 0]), None);
+        assert_eq!(Measurement::from_bytes([0xFB, 0x00, 2, 1]), None);
+    }
+
+    #[test]
+    fn run_continuous_past_data_not_ready() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        bus.conversion_polls = 3;
+        let mut samples = 0;
+        Tmp117::new(&mut bus, 0x48)
+            .run_continuous(Default::default(), |t| {
+                assert_eq!(t, 25.0);
+                samples += 1;
+                if samples == 3 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        assert_eq!(samples, 3);
+        let not_ready = bus
+            .transactions()
+            .iter()
+            .filter(|t| {
+                matches!(t, Transaction::Read { register: 0x01, value, .. } if value & 0x2000 == 0)
+            })
+            .count();
+        // Three reads without data before each sample, the others are from the edits of the mode
+        assert!(not_ready >= 9);
+    }
+
+    #[test]
+    fn control_state_walks_band() {
+        let mut bus = Bus::new();
+        // Up through the band then back down, in °C: 23, 24.5, 25.5, 26.5, 25.5, 24.5, 23.5
+        let walk = [
+            (0x0B80, true),
+            (0x0C40, true),
+            (0x0CC0, true),
+            (0x0D40, false),
+            (0x0CC0, false),
+            (0x0C40, false),
+            (0x0BC0, true),
+        ];
+        for (raw, _) in walk {
+            bus.queue_read(0x00, raw);
+        }
+        Tmp117::new(&mut bus, 0x48)
+            .resume_continuous(|mut t| {
+                let mut heat = false;
+                for (_, expected) in walk {
+                    heat = t.control_state(Celsius(25.0), Celsius(1.0), heat)?;
+                    assert_eq!(heat, expected);
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn alert_limits_try_around() {
+        let limits = AlertLimits::try_around::<(), ()>(Celsius(25.0), Celsius(5.0)).unwrap();
+        assert_eq!(limits.high, HighLimit::from(0x0F00));
+        assert_eq!(limits.low, LowLimit::from(0x0A00));
+        assert_eq!(
+            AlertLimits::try_around::<(), ()>(Celsius(250.0), Celsius(10.0)),
+            Err(Error::LimitOutOfRange)
+        );
+        assert_eq!(
+            AlertLimits::try_around::<(), ()>(Celsius(-250.0), Celsius(10.0)),
+            Err(Error::LimitOutOfRange)
+        );
+    }
+
+    #[test]
+    fn liveness_identical_and_differing() {
+        let mut bus = Bus::new();
+        bus.queue_read(0x00, 0x0C80);
+        bus.queue_read(0x00, 0x0C80);
+        bus.queue_read(0x00, 0x0C80);
+        bus.queue_read(0x00, 0x0C81);
+        let mut delay = MockDelay::new();
+        Tmp117::new(&mut bus, 0x48)
+            .resume_continuous(|mut t| {
+                assert_eq!(t.read_temp_liveness(&mut delay), Err(Error::StuckSensor));
+                assert_eq!(
+                    t.read_temp_liveness(&mut delay),
+                    Ok(Celsius(decode_temperature(0x0C81)))
+                );
+                Ok(())
+            })
+            .unwrap();
+        // Waits just over the 1 s period of the power up configuration
+        assert_eq!(delay.ns, 2 * 1_001_000_000);
+    }
+
+    #[test]
+    fn configured_polarity_kept() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0400;
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        let pin = AlertPinConfig {
+            active_high: true,
+            pull_up: true,
+        };
+        tmp.set_alert_pin_config(&pin).unwrap();
+        tmp.set_alert_pin_select(AlertPinSelect::DataReady).unwrap();
+        tmp.oneshot(Average::NoAverage).unwrap();
+        tmp.continuous(Default::default(), |_| Ok(())).unwrap();
+        assert_eq!(bus.registers[1] & 0x000C, 0x000C);
+        let polarity_writes = bus
+            .transactions()
+            .iter()
+            .filter(|t| matches!(t, Transaction::Write { register: 0x01, value, .. } if value & 0x0008 == 0))
+            .count();
+        assert_eq!(polarity_writes, 0);
+    }
+
+    #[test]
+    fn unreachable_polarity_rejected() {
+        let mut bus = Bus::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        for active_high in [false, true] {
+            let pin = AlertPinConfig {
+                active_high,
+                pull_up: false,
+            };
+            assert_eq!(
+                tmp.set_alert_pin_config(&pin),
+                Err(Error::UnreachablePolarity)
+            );
+        }
+        assert!(bus.transactions().is_empty());
+    }
+
+    #[test]
+    fn current_conversion_period_adopted() {
+        for (raw, period) in [(0x03E0, 16000), (0x0040, 500), (0x00A0, 125), (0x0000, 16)] {
+            let mut bus = Bus::new();
+            bus.registers[1] = raw;
+            let (mut tmp, _) = Tmp117::adopt(&mut bus, 0x48).unwrap();
+            assert_eq!(tmp.current_conversion_period_ms(), Ok(period));
+            assert_eq!(bus.writes(0x01), 0);
+        }
+    }
+
+    #[test]
+    fn write_eeprom_verified_reloads_words() {
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0400;
+        bus.program_polls = 2;
+        let mut delay = MockDelay::new();
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        tmp.write_eeprom_verified([1, 2, 3], &mut delay).unwrap();
+        assert_eq!(bus.eeprom[5..8], [1, 2, 3]);
+        // Locked again and in shutdown after the reset
+        assert_eq!(bus.registers[4] & 0x8000, 0);
+        assert_eq!(bus.registers[1] & 0x0C00, 0x0400);
+
+        // A word failing to program is only seen once reloaded from the eeprom
+        let mut bus = Bus::new();
+        bus.registers[1] = 0x0400;
+        bus.broken_eeprom = Some(6);
+        let stored = bus.eeprom[6];
+        let mut tmp = Tmp117::new(&mut bus, 0x48);
+        assert_eq!(
+            tmp.write_eeprom_verified([1, 2, 3], &mut delay),
+            Err(Error::EepromVerifyFailed {
+                index: 1,
+                expected: 2,
+                got: stored
+            })
+        );
+    }
+
+    #[test]
+    fn alert_config_round_trip() {
+        for alert in [
+            AlertConfig {
+                pin_select: AlertPinSelect::DataReady,
+                polarity: Polarity::ActiveHigh,
+                trigger_mode: TriggerMode::Alert,
+            },
+            AlertConfig {
+                pin_select: AlertPinSelect::Alert,
+                polarity: Polarity::ActiveLow,
+                trigger_mode: TriggerMode::Thermal,
+            },
+        ] {
+            let mut bus = Bus::new();
+            let mut tmp = Tmp117::new(&mut bus, 0x48);
+            tmp.write_alert_config(&alert).unwrap();
+            assert_eq!(tmp.read_alert_config(), Ok(alert));
+            // The write is a single read-modify-write, then a single read
+            assert_eq!(bus.reads(0x01), 2);
+            assert_eq!(bus.writes(0x01), 1);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn postcard_round_trip() {
+        let snapshot = ConfigSnapshot {
+            configuration: Configuration::try_from(0x0494).unwrap(),
+            high_limit: Celsius(30.0),
+            low_limit: Celsius(-10.5),
+            offset: Celsius(0.25),
+        };
+        let mut buf = [0; 32];
+        let bytes = postcard::to_slice(&snapshot, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes::<ConfigSnapshot>(bytes), Ok(snapshot));
+
+        let measurement = Measurement {
+            raw: -1280,
+            alert: Alert::High,
+        };
+        let bytes = postcard::to_slice(&measurement, &mut buf).unwrap();
+        assert_eq!(postcard::from_bytes::<Measurement>(bytes), Ok(measurement));
+    }
+
+    #[test]
+    fn user_store_magic() {
+        const MAGIC: u16 = 0xC0DE;
+        let mut bus = Bus::new();
+        bus.registers[5..8].copy_from_slice(&[MAGIC, 2, 0x1234]);
+        let store = Tmp117::new(&mut bus, 0x48)
+            .read_user_eeprom_typed()
+            .unwrap();
+        assert!(store.validate(MAGIC));
+        assert_eq!(
+            store,
+            UserStore {
+                magic: MAGIC,
+                version: 2,
+                payload: 0x1234
+            }
+        );
+        assert_eq!(store.to_words(), [MAGIC, 2, 0x1234]);
+
+        // A NIST ID from the factory is not the magic word
+        bus.registers[5] = 0x5A17;
+        let store = Tmp117::new(&mut bus, 0x48)
+            .read_user_eeprom_typed()
+            .unwrap();
+        assert!(!store.validate(MAGIC));
+    }
+
+    #[test]
+    fn diagnose_outcomes() {
+        let mut bus = Bus::new();
+        assert_eq!(
+            Tmp117::new(&mut bus, 0x48).diagnose(),
+            Diagnosis::Ok { revision: 1 }
+        );
+
+        bus.registers[15] = 0x2190;
+        assert_eq!(
+            Tmp117::new(&mut bus, 0x48).diagnose(),
+            Diagnosis::WrongDevice {
+                id: Id {
+                    device: 0x190,
+                    revision: 2
+                }
+            }
+        );
+
+        bus.fail_next(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        assert_eq!(Tmp117::new(&mut bus, 0x48).diagnose(), Diagnosis::NoDevice);
+
+        bus.fail_next(ErrorKind::ArbitrationLoss);
+        assert_eq!(
+            Tmp117::new(&mut bus, 0x48).diagnose(),
+            Diagnosis::BusFault(ErrorKind::ArbitrationLoss)
+        );
+    }
+
+    #[test]
+    fn read_temp_delayed_between_reads() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        let mut delay = ReadDelay::new(MockDelay::new(), 500);
+        Tmp117::new(&mut bus, 0x48)
+            .resume_continuous(|mut t| {
+                // The first read is not delayed, the next ones are
+                for _ in 0..3 {
+                    assert_eq!(t.read_temp_delayed(&mut delay), Ok(25.0));
+                }
+                delay.reset();
+                t.read_temp_delayed(&mut delay)?;
+                Ok(())
+            })
+            .unwrap();
+        let mock = delay.release();
+        assert_eq!(mock.calls, 2);
+        assert_eq!(mock.ns, 2 * 500_000);
+        assert_eq!(bus.reads(0x00), 4);
+    }
+
+    #[test]
+    fn read_temp_delayed_after_failed_read() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        // Read by resume_continuous, then a read without the data ready flag
+        bus.queue_read(0x01, 0x0220);
+        bus.queue_read(0x01, 0x0220);
+        let mut delay = ReadDelay::new(MockDelay::new(), 500);
+        Tmp117::new(&mut bus, 0x48)
+            .resume_continuous(|mut t| {
+                assert_eq!(t.read_temp_delayed(&mut delay), Err(Error::DataNotReady));
+                // The failed read does not delay the next one
+                assert_eq!(t.read_temp_delayed(&mut delay), Ok(25.0));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(delay.release().calls, 0);
+    }
 }
@@ -2,9 +2,13 @@
 #![allow(clippy::identity_op)]
 #![allow(missing_docs)]
 
+use core::fmt;
+
 use bilge::prelude::*;
 use device_register::{RERegister, RORegister, RWRegister};
 
+use crate::{Error, CELCIUS_CONVERSION};
+
 /// The address of the register
 pub struct Address(pub u8);
 
@@ -41,12 +45,15 @@ pub enum Polarity {
     ActiveHigh = 1,
 }
 
-/// Possible mode selection
+/// Possible mode selection.
+/// Independent of the [AlertPinSelect], the pin can reflect the data ready flag while the
+/// comparisons are still done in thermal mode, which is a valid combination per the datasheet
 #[bitsize(1)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(Copy, Clone, PartialEq, Eq, Debug, FromBits)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, FromBits)]
 pub enum TriggerMode {
     /// Alert mode
+    #[default]
     Alert = 0,
 
     /// Thermal mode
@@ -59,9 +66,10 @@ pub enum TriggerMode {
 /// accumulated average and not a running average.
 #[bitsize(2)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(Copy, Clone, PartialEq, Eq, Debug, FromBits)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, FromBits)]
 pub enum Average {
     /// No averaging
+    #[default]
     NoAverage = 0,
 
     /// 8 averaged conversions
@@ -74,9 +82,23 @@ pub enum Average {
     Avg64 = 3,
 }
 
-impl Default for Average {
-    fn default() -> Self {
-        Self::NoAverage
+impl Average {
+    /// Returns the next average with more conversions, saturates at [Average::Avg64]
+    pub fn next(self) -> Self {
+        match self {
+            Average::NoAverage => Average::Avg8,
+            Average::Avg8 => Average::Avg32,
+            Average::Avg32 | Average::Avg64 => Average::Avg64,
+        }
+    }
+
+    /// Returns the previous average with less conversions, saturates at [Average::NoAverage]
+    pub fn prev(self) -> Self {
+        match self {
+            Average::NoAverage | Average::Avg8 => Average::NoAverage,
+            Average::Avg32 => Average::Avg8,
+            Average::Avg64 => Average::Avg32,
+        }
     }
 }
 
@@ -93,9 +115,10 @@ impl Default for Average {
 /// | 111       | 16 S          | 16 S          | 16 S          | 16 S          |
 #[bitsize(3)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(Copy, Clone, PartialEq, Eq, Debug, FromBits)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, FromBits)]
 pub enum Conversion {
     /// 15.5ms cycle time without average.
+    #[default]
     Ms15_5 = 0,
 
     /// 125ms cycle time without average.
@@ -119,12 +142,67 @@ pub enum Conversion {
     /// 16000ms cycle time without average.
     Ms16000 = 7,
 }
-impl Default for Conversion {
-    fn default() -> Self {
-        Self::Ms15_5
+
+impl Conversion {
+    /// Returns the next longer conversion cycle, saturates at [Conversion::Ms16000]
+    pub fn next(self) -> Self {
+        match self {
+            Conversion::Ms15_5 => Conversion::Ms125,
+            Conversion::Ms125 => Conversion::Ms250,
+            Conversion::Ms250 => Conversion::Ms500,
+            Conversion::Ms500 => Conversion::Ms1000,
+            Conversion::Ms1000 => Conversion::Ms4000,
+            Conversion::Ms4000 => Conversion::Ms8000,
+            Conversion::Ms8000 | Conversion::Ms16000 => Conversion::Ms16000,
+        }
+    }
+
+    /// Returns the previous shorter conversion cycle, saturates at [Conversion::Ms15_5]
+    pub fn prev(self) -> Self {
+        match self {
+            Conversion::Ms15_5 | Conversion::Ms125 => Conversion::Ms15_5,
+            Conversion::Ms250 => Conversion::Ms125,
+            Conversion::Ms500 => Conversion::Ms250,
+            Conversion::Ms1000 => Conversion::Ms500,
+            Conversion::Ms4000 => Conversion::Ms1000,
+            Conversion::Ms8000 => Conversion::Ms4000,
+            Conversion::Ms16000 => Conversion::Ms8000,
+        }
+    }
+}
+
+/// Returns the effective conversion cycle time in milliseconds for a conversion cycle and average,
+/// as described in the [Conversion] table. The 15.5 ms cycle is rounded up to 16 ms.
+pub const fn conversion_period_ms(conversion: Conversion, average: Average) -> u32 {
+    let conversion_ms = match conversion {
+        Conversion::Ms15_5 => 16,
+        Conversion::Ms125 => 125,
+        Conversion::Ms250 => 250,
+        Conversion::Ms500 => 500,
+        Conversion::Ms1000 => 1000,
+        Conversion::Ms4000 => 4000,
+        Conversion::Ms8000 => 8000,
+        Conversion::Ms16000 => 16000,
+    };
+    let average_ms = match average {
+        Average::NoAverage => 16,
+        Average::Avg8 => 125,
+        Average::Avg32 => 500,
+        Average::Avg64 => 1000,
+    };
+
+    if conversion_ms > average_ms {
+        conversion_ms
+    } else {
+        average_ms
     }
 }
 
+// Known periods, checked at compile time
+const _: () = assert!(conversion_period_ms(Conversion::Ms15_5, Average::NoAverage) == 16);
+const _: () = assert!(conversion_period_ms(Conversion::Ms125, Average::Avg32) == 500);
+const _: () = assert!(conversion_period_ms(Conversion::Ms4000, Average::Avg64) == 4000);
+
 /// Conversion mode
 #[bitsize(2)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -197,6 +275,46 @@ pub struct Configuration {
     pub high_alert: bool,
 }
 
+impl Configuration {
+    /// Decode a configuration, rejecting values with the reserved bit set or an invalid conversion mode.
+    /// Useful to validate a stored or adopted configuration before writing it back
+    pub fn try_from_u16(value: u16) -> Result<Self, InvalidConfiguration> {
+        if value & 0x0001 != 0 {
+            return Err(InvalidConfiguration::ReservedBit);
+        }
+        Self::try_from(value).map_err(|_| InvalidConfiguration::InvalidMode)
+    }
+}
+
+/// Error returned when decoding an invalid [Configuration] with [Configuration::try_from_u16]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InvalidConfiguration {
+    /// The reserved bit 0 is set
+    ReservedBit,
+
+    /// The conversion mode bits hold the reserved value `0b10`
+    InvalidMode,
+}
+
+impl fmt::Display for InvalidConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidConfiguration::ReservedBit => f.write_str("reserved bit set"),
+            InvalidConfiguration::InvalidMode => f.write_str("invalid conversion mode"),
+        }
+    }
+}
+
+impl<E, PE> From<InvalidConfiguration> for Error<E, PE> {
+    fn from(_: InvalidConfiguration) -> Self {
+        Error::InvalidData
+    }
+}
+
+/// Bits of the [Configuration] register that can be written, the others are read only or reserved
+pub const CONFIGURATION_WRITABLE_MASK: u16 = 0x0FFE;
+
 /// The high limit register is a 16-bit, read/write register that stores the high limit for comparison with the temperature result.
 /// One LSB equals 7.8125 m°C. The range of the register is ±256 °C. Negative numbers are represented in binary
 /// two's complement format. Following power-up or a general-call reset, the high-limit register is loaded with the
@@ -227,7 +345,7 @@ pub struct EEPROM {
     reserved: u14,
 
     /// EEPROM busy flag, either caused by programming or power-up
-    ///Mirror the `eeprom_busy` in the [Configuration](Configuration) register
+    ///Mirror the `eeprom_busy` in the [Configuration](Configuration) register, which is the one used by the drivers
     pub busy: bool,
 
     /// If the eeprom is unlock. If unlocked, any writes to the registers program will be written to the eeprom
@@ -270,6 +388,98 @@ pub struct UEEPROM3(pub u16);
 #[register(ty = "Address", addr = "Address(0x08)")]
 pub struct TemperatureOffset(pub u16);
 
+/// Converts a temperature in m°C to the raw two's complement value used by the [Temperature],
+/// [HighLimit], [LowLimit] and [TemperatureOffset] registers. Saturates outside of ±256 °C.
+/// Only uses integer math so register images can be computed in `const` contexts.
+pub const fn millicelsius_to_raw(millicelsius: i32) -> u16 {
+    let raw = millicelsius as i64 * 128 / 1000;
+    if raw > i16::MAX as i64 {
+        i16::MAX as u16
+    } else if raw < i16::MIN as i64 {
+        i16::MIN as u16
+    } else {
+        raw as i16 as u16
+    }
+}
+
+/// Converts the raw two's complement value of the [Temperature], [HighLimit], [LowLimit]
+/// and [TemperatureOffset] registers to a temperature in m°C. Only uses integer math.
+pub const fn raw_to_millicelsius(raw: u16) -> i32 {
+    raw as i16 as i32 * 1000 / 128
+}
+
+/// Offset between celsius and kelvin, in m°C
+pub const MILLIKELVIN_OFFSET: i32 = 273_150;
+
+/// Converts the raw two's complement value of the [Temperature] register to a temperature in mK,
+/// which is the temperature in m°C plus the fixed [MILLIKELVIN_OFFSET]. Only uses integer math.
+pub const fn raw_to_millikelvin(raw: u16) -> i32 {
+    raw_to_millicelsius(raw) + MILLIKELVIN_OFFSET
+}
+
+/// Converts a temperature in celsius to the raw two's complement value used by the [Temperature],
+/// [HighLimit], [LowLimit] and [TemperatureOffset] registers. Rounds to the nearest lsb and saturates outside of ±256 °C.
+pub fn celsius_to_raw(celsius: f32) -> u16 {
+    let lsb = celsius / CELCIUS_CONVERSION;
+    // The cast truncates toward zero, so offset by half an lsb away from zero to round
+    let rounded = if lsb < 0.0 { lsb - 0.5 } else { lsb + 0.5 };
+    rounded as i16 as u16
+}
+
+/// Converts a temperature in celsius to the raw two's complement value like [celsius_to_raw],
+/// but returns [Error::LimitOutOfRange] outside of ±256 °C instead of saturating
+pub fn try_celsius_to_raw<E, PE>(celsius: f32) -> Result<i16, Error<E, PE>> {
+    if !(-256.0..=256.0).contains(&celsius) {
+        return Err(Error::LimitOutOfRange);
+    }
+    Ok(celsius_to_raw(celsius) as i16)
+}
+
+impl HighLimit {
+    /// Creates the register value from a temperature in celsius
+    pub fn from_celsius(celsius: f32) -> Self {
+        celsius_to_raw(celsius).into()
+    }
+
+    /// Creates the register value from a temperature in celsius, returns [Error::LimitOutOfRange] outside of ±256 °C
+    pub fn try_from_celsius<E, PE>(celsius: f32) -> Result<Self, Error<E, PE>> {
+        Ok((try_celsius_to_raw(celsius)? as u16).into())
+    }
+}
+
+impl LowLimit {
+    /// Creates the register value from a temperature in celsius
+    pub fn from_celsius(celsius: f32) -> Self {
+        celsius_to_raw(celsius).into()
+    }
+
+    /// Creates the register value from a temperature in celsius, returns [Error::LimitOutOfRange] outside of ±256 °C
+    pub fn try_from_celsius<E, PE>(celsius: f32) -> Result<Self, Error<E, PE>> {
+        Ok((try_celsius_to_raw(celsius)? as u16).into())
+    }
+}
+
+impl TemperatureOffset {
+    /// Creates the register value from a temperature in celsius
+    pub fn from_celsius(celsius: f32) -> Self {
+        celsius_to_raw(celsius).into()
+    }
+
+    /// Creates the register value from a temperature in celsius, returns [Error::LimitOutOfRange] outside of ±256 °C
+    pub fn try_from_celsius<E, PE>(celsius: f32) -> Result<Self, Error<E, PE>> {
+        Ok((try_celsius_to_raw(celsius)? as u16).into())
+    }
+}
+
+// Known mappings, checked at compile time
+const _: () = assert!(millicelsius_to_raw(30_000) == 0x0F00);
+const _: () = assert!(millicelsius_to_raw(-256_000) == 0x8000);
+const _: () = assert!(millicelsius_to_raw(192_000) == 0x6000);
+const _: () = assert!(millicelsius_to_raw(300_000) == 0x7FFF);
+const _: () = assert!(raw_to_millicelsius(0xEC00) == -40_000);
+const _: () = assert!(raw_to_millikelvin(0x0000) == 273_150);
+const _: () = assert!(raw_to_millikelvin(0xEC00) == 233_150);
+
 /// Indicates the device ID
 #[bitsize(16)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -282,3 +492,89 @@ pub struct DeviceID {
     /// Indicates the revision number
     pub revision: u4,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_walk() {
+        let up = [
+            Average::Avg8,
+            Average::Avg32,
+            Average::Avg64,
+            Average::Avg64,
+        ];
+        let mut avg = Average::NoAverage;
+        for expected in up {
+            avg = avg.next();
+            assert_eq!(avg, expected);
+        }
+        let down = [
+            Average::Avg32,
+            Average::Avg8,
+            Average::NoAverage,
+            Average::NoAverage,
+        ];
+        for expected in down {
+            avg = avg.prev();
+            assert_eq!(avg, expected);
+        }
+    }
+
+    #[test]
+    fn conversion_walk() {
+        let all = [
+            Conversion::Ms15_5,
+            Conversion::Ms125,
+            Conversion::Ms250,
+            Conversion::Ms500,
+            Conversion::Ms1000,
+            Conversion::Ms4000,
+            Conversion::Ms8000,
+            Conversion::Ms16000,
+        ];
+        for pair in all.windows(2) {
+            assert_eq!(pair[0].next(), pair[1]);
+            assert_eq!(pair[1].prev(), pair[0]);
+        }
+        assert_eq!(Conversion::Ms16000.next(), Conversion::Ms16000);
+        assert_eq!(Conversion::Ms15_5.prev(), Conversion::Ms15_5);
+    }
+
+    #[test]
+    fn configuration_try_from_u16() {
+        let config = Configuration::try_from_u16(0x0220).unwrap();
+        assert_eq!(config.mode(), ConversionMode::Continuous);
+        assert_eq!(config.average(), Average::Avg8);
+        assert_eq!(
+            Configuration::try_from_u16(0x0221),
+            Err(InvalidConfiguration::ReservedBit)
+        );
+        assert_eq!(
+            Configuration::try_from_u16(0x0820),
+            Err(InvalidConfiguration::InvalidMode)
+        );
+    }
+
+    #[test]
+    fn try_celsius_to_raw_boundaries() {
+        type E = Error<(), ()>;
+        assert_eq!(try_celsius_to_raw::<(), ()>(256.0), Ok(i16::MAX));
+        assert_eq!(try_celsius_to_raw::<(), ()>(-256.0), Ok(i16::MIN));
+        for celsius in [256.01, -256.01, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                try_celsius_to_raw(celsius),
+                Err::<i16, E>(Error::LimitOutOfRange)
+            );
+        }
+        assert_eq!(
+            HighLimit::try_from_celsius::<(), ()>(-256.0),
+            Ok(HighLimit::from(0x8000))
+        );
+        assert_eq!(
+            LowLimit::try_from_celsius(256.5),
+            Err::<LowLimit, E>(Error::LimitOutOfRange)
+        );
+    }
+}
@@ -0,0 +1,671 @@
+//! Utilities to test code using the drivers without a device.
+//! Enable the `test-util` feature to use them, usually as a dev-dependency:
+//! `tmp117 = { version = "2", features = ["test-util"] }`
+//!
+//! [RecordingI2c] emulates the registers of a TMP117 closely enough to run the drivers against it,
+//! [MockDelay] and [MockPin] stand in for the delay and the alert pin, and [block_on] runs the async drivers
+use core::{
+    future::Future,
+    pin::{pin, Pin},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use embedded_hal::i2c::{ErrorKind, ErrorType, Operation, SevenBitAddress};
+
+/// A transaction recorded by the [RecordingI2c]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Transaction {
+    /// A register was read
+    Read {
+        /// The i2c address of the device
+        addr: u8,
+        /// The address of the register
+        register: u8,
+        /// The value read
+        value: u16,
+    },
+
+    /// A register was written
+    Write {
+        /// The i2c address of the device
+        addr: u8,
+        /// The address of the register
+        register: u8,
+        /// The value written
+        value: u16,
+    },
+}
+
+const TEMPERATURE: usize = 0x00;
+const CONFIGURATION: usize = 0x01;
+const HIGH_LIMIT: usize = 0x02;
+const LOW_LIMIT: usize = 0x03;
+const EEPROM: usize = 0x04;
+
+/// Registers loaded from the eeprom on reset
+const EEPROM_BACKED: [usize; 7] = [1, 2, 3, 5, 6, 7, 8];
+
+const DATA_READY: u16 = 1 << 13;
+const LOW_ALERT: u16 = 1 << 14;
+const HIGH_ALERT: u16 = 1 << 15;
+const EEPROM_BUSY: u16 = 1 << 12;
+const RESET: u16 = 1 << 1;
+const THERMAL: u16 = 1 << 4;
+const WRITABLE: u16 = 0x0FFE;
+const EEPROM_UNLOCK: u16 = 1 << 15;
+const EEPROM_REGISTER_BUSY: u16 = 1 << 14;
+
+const MODE_SHIFT: u16 = 10;
+const MODE_MASK: u16 = 0b11 << MODE_SHIFT;
+const CONTINUOUS: u16 = 0b00;
+const SHUTDOWN: u16 = 0b01;
+const ONESHOT: u16 = 0b11;
+
+/// Value of the temperature register after a reset, until the first conversion
+const RESET_TEMPERATURE: u16 = 0x8000;
+
+/// Register values at power up, with the factory eeprom and a revision 1 device
+const POWER_ON: [u16; 16] = [
+    RESET_TEMPERATURE,
+    0x0220,
+    0x6000,
+    0x8000,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0,
+    0x1117,
+];
+
+/// An i2c bus emulating the registers of a TMP117 and recording the register accesses,
+/// allowing to assert the exact bus behavior of the drivers.
+/// Can record up to `N` transactions, panics if more are made.
+///
+/// Emulates the behavior the drivers rely on:
+/// - reading the configuration clears the data ready flag and, in alert mode, the alert flags
+/// - reading the temperature clears the data ready flag
+/// - a conversion completes every [conversion_polls](Self::conversion_polls) reads of the configuration,
+///   right away when it is 0. After a oneshot, the device goes back to shutdown with the data ready
+/// - writes while the eeprom is unlocked program it and keep it busy for [program_polls](Self::program_polls) reads
+/// - a reset reloads the registers from the eeprom
+pub struct RecordingI2c<const N: usize> {
+    /// The values of the emulated registers, indexed by address
+    pub registers: [u16; 16],
+
+    /// The values programmed in the eeprom, loaded in the registers on reset. Indexed like the registers
+    pub eeprom: [u16; 16],
+
+    /// The raw temperature sensed, stored in the temperature register at each conversion
+    pub temperature: u16,
+
+    /// Number of reads of the configuration reporting no new data before each conversion
+    pub conversion_polls: u8,
+
+    /// Number of reads of the configuration reporting the eeprom busy after programming a word or a reset
+    pub program_polls: u8,
+
+    /// A register whose eeprom word fails to program, to emulate a marginal supply
+    pub broken_eeprom: Option<u8>,
+
+    pointer: u8,
+    polls: u8,
+    busy: u8,
+    error: Option<ErrorKind>,
+    queued: heapless::Vec<(u8, u16), 16>,
+    transactions: heapless::Vec<Transaction, N>,
+}
+
+impl<const N: usize> Default for RecordingI2c<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RecordingI2c<N> {
+    /// Create a new bus with a device just powered up with the factory eeprom,
+    /// a revision 1 device in continuous mode with the power up temperature
+    pub fn new() -> Self {
+        Self {
+            registers: POWER_ON,
+            eeprom: POWER_ON,
+            temperature: 0,
+            conversion_polls: 0,
+            program_polls: 0,
+            broken_eeprom: None,
+            pointer: 0,
+            polls: 0,
+            busy: 0,
+            error: None,
+            queued: heapless::Vec::new(),
+            transactions: heapless::Vec::new(),
+        }
+    }
+
+    /// Returns the transactions recorded so far
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Returns the number of reads of the register recorded so far
+    pub fn reads(&self, register: u8) -> usize {
+        self.transactions
+            .iter()
+            .filter(|t| matches!(t, Transaction::Read { register: r, .. } if *r == register))
+            .count()
+    }
+
+    /// Returns the number of writes of the register recorded so far
+    pub fn writes(&self, register: u8) -> usize {
+        self.transactions
+            .iter()
+            .filter(|t| matches!(t, Transaction::Write { register: r, .. } if *r == register))
+            .count()
+    }
+
+    /// Clear the recorded transactions
+    pub fn clear(&mut self) {
+        self.transactions.clear();
+    }
+
+    /// Fail the next transaction with the error, nothing is recorded for it
+    pub fn fail_next(&mut self, error: ErrorKind) {
+        self.error = Some(error);
+    }
+
+    /// Return the value on the next read of the register instead of the emulated one,
+    /// to emulate a corrupted read for example. The side effects of the read still apply
+    pub fn queue_read(&mut self, register: u8, value: u16) {
+        self.queued
+            .push((register, value))
+            .expect("read queue is full");
+    }
+
+    fn record(&mut self, transaction: Transaction) {
+        self.transactions
+            .push(transaction)
+            .expect("transaction log is full");
+    }
+
+    fn mode(&self) -> u16 {
+        (self.registers[CONFIGURATION] & MODE_MASK) >> MODE_SHIFT
+    }
+
+    fn set_mode(&mut self, mode: u16) {
+        self.registers[CONFIGURATION] =
+            (self.registers[CONFIGURATION] & !MODE_MASK) | mode << MODE_SHIFT;
+    }
+
+    /// Completes a conversion, updating the temperature and the flags
+    fn convert(&mut self) {
+        let temp = self.temperature as i16;
+        let high = self.registers[HIGH_LIMIT] as i16;
+        let low = self.registers[LOW_LIMIT] as i16;
+        self.registers[TEMPERATURE] = self.temperature;
+
+        let config = &mut self.registers[CONFIGURATION];
+        *config |= DATA_READY;
+        if *config & THERMAL != 0 {
+            if temp > high {
+                *config |= HIGH_ALERT;
+            } else if temp < low {
+                *config &= !HIGH_ALERT;
+            }
+        } else {
+            if temp > high {
+                *config |= HIGH_ALERT;
+            }
+            if temp < low {
+                *config |= LOW_ALERT;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for r in EEPROM_BACKED {
+            self.registers[r] = self.eeprom[r];
+        }
+        self.registers[CONFIGURATION] &= WRITABLE & !RESET;
+        self.registers[TEMPERATURE] = RESET_TEMPERATURE;
+        self.registers[EEPROM] = 0;
+        self.busy = self.program_polls;
+        self.polls = 0;
+    }
+
+    fn program(&mut self, register: usize, value: u16) {
+        if self.broken_eeprom != Some(register as u8) {
+            self.eeprom[register] = value;
+        }
+        self.busy = self.program_polls;
+    }
+
+    fn write_register(&mut self, register: usize, value: u16) {
+        let unlocked = self.registers[EEPROM] & EEPROM_UNLOCK != 0;
+        match register {
+            CONFIGURATION => {
+                let config = &mut self.registers[CONFIGURATION];
+                *config = (*config & !WRITABLE) | (value & WRITABLE & !RESET);
+                if value & RESET != 0 {
+                    self.reset();
+                    return;
+                }
+                if unlocked {
+                    self.program(register, value & WRITABLE & !RESET);
+                }
+                match self.mode() {
+                    ONESHOT if self.conversion_polls == 0 => {
+                        self.convert();
+                        self.set_mode(SHUTDOWN);
+                    }
+                    ONESHOT | CONTINUOUS => self.polls = 0,
+                    _ => {}
+                }
+            }
+            EEPROM => self.registers[EEPROM] = value & EEPROM_UNLOCK,
+            2 | 3 | 5..=8 => {
+                self.registers[register] = value;
+                if unlocked {
+                    self.program(register, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_register(&mut self, register: usize) -> u16 {
+        let mut value = match register {
+            CONFIGURATION => {
+                let mode = self.mode();
+                if mode == CONTINUOUS || mode == ONESHOT {
+                    if self.polls >= self.conversion_polls {
+                        self.convert();
+                        self.polls = 0;
+                        if mode == ONESHOT {
+                            self.set_mode(SHUTDOWN);
+                        }
+                    } else {
+                        self.polls += 1;
+                    }
+                }
+
+                let busy = if self.busy > 0 {
+                    self.busy -= 1;
+                    EEPROM_BUSY
+                } else {
+                    0
+                };
+                let value = self.registers[CONFIGURATION] | busy;
+
+                let clear = if value & THERMAL != 0 {
+                    DATA_READY
+                } else {
+                    DATA_READY | LOW_ALERT | HIGH_ALERT
+                };
+                self.registers[CONFIGURATION] &= !clear;
+                value
+            }
+            TEMPERATURE => {
+                self.registers[CONFIGURATION] &= !DATA_READY;
+                self.registers[TEMPERATURE]
+            }
+            EEPROM if self.busy > 0 => self.registers[EEPROM] | EEPROM_REGISTER_BUSY,
+            _ => self.registers[register],
+        };
+
+        if let Some(i) = self
+            .queued
+            .iter()
+            .position(|(r, _)| *r as usize == register)
+        {
+            value = self.queued.remove(i).1;
+        }
+        value
+    }
+
+    fn run(&mut self, addr: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        for op in operations {
+            match op {
+                Operation::Write(bytes) => {
+                    if let Some(register) = bytes.first() {
+                        self.pointer = register & 0x0F;
+                    }
+                    match bytes {
+                        [_] => {}
+                        [_, hi, lo] => {
+                            let value = u16::from_be_bytes([*hi, *lo]);
+                            self.write_register(self.pointer as usize, value);
+                            self.record(Transaction::Write {
+                                addr,
+                                register: self.pointer,
+                                value,
+                            });
+                        }
+                        _ => panic!("unexpected write of {} bytes", bytes.len()),
+                    }
+                }
+                Operation::Read(buff) => {
+                    // The registers are 16 bits, any other length is a driver bug
+                    assert_eq!(buff.len(), 2, "unexpected read of {} bytes", buff.len());
+                    let value = self.read_register(self.pointer as usize);
+                    buff.copy_from_slice(&value.to_be_bytes());
+                    self.record(Transaction::Read {
+                        addr,
+                        register: self.pointer,
+                        value,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> ErrorType for RecordingI2c<N> {
+    type Error = ErrorKind;
+}
+
+impl<const N: usize> embedded_hal::i2c::I2c<SevenBitAddress> for RecordingI2c<N> {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.run(address, operations)
+    }
+}
+
+impl<const N: usize> embedded_hal_async::i2c::I2c<SevenBitAddress> for RecordingI2c<N> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.run(address, operations)
+    }
+}
+
+/// A delay returning right away and counting the time asked, implements both the blocking and async delays
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct MockDelay {
+    /// Number of delays
+    pub calls: usize,
+
+    /// Total time of the delays in nanoseconds
+    pub ns: u64,
+
+    /// If set, each async delay is pending once before completing like a timer would
+    pub yields: bool,
+}
+
+impl MockDelay {
+    /// Create a new delay with no time counted
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl embedded_hal::delay::DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.calls += 1;
+        self.ns += ns as u64;
+    }
+}
+
+impl embedded_hal_async::delay::DelayNs for MockDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.calls += 1;
+        self.ns += ns as u64;
+        if self.yields {
+            YieldOnce(false).await;
+        }
+    }
+}
+
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// An alert pin whose waits complete right away, counting them
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct MockPin {
+    /// Number of waits for a low level or a falling edge
+    pub low_waits: usize,
+
+    /// Number of waits for a high level or a rising edge
+    pub high_waits: usize,
+
+    /// Number of waits for any edge
+    pub edge_waits: usize,
+
+    error: Option<embedded_hal::digital::ErrorKind>,
+}
+
+impl MockPin {
+    /// Create a new pin with no wait counted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the next wait with the error
+    pub fn fail_next(&mut self, error: embedded_hal::digital::ErrorKind) {
+        self.error = Some(error);
+    }
+
+    fn wait(&mut self) -> Result<(), embedded_hal::digital::ErrorKind> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl embedded_hal::digital::ErrorType for MockPin {
+    type Error = embedded_hal::digital::ErrorKind;
+}
+
+impl embedded_hal_async::digital::Wait for MockPin {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.high_waits += 1;
+        self.wait()
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.low_waits += 1;
+        self.wait()
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.high_waits += 1;
+        self.wait()
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.low_waits += 1;
+        self.wait()
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        self.edge_waits += 1;
+        self.wait()
+    }
+}
+
+fn noop_raw_waker() -> RawWaker {
+    fn clone(_: *const ()) -> RawWaker {
+        noop_raw_waker()
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    RawWaker::new(core::ptr::null(), &VTABLE)
+}
+
+/// Poll the future once with a waker doing nothing
+pub fn poll_once<F: Future>(fut: Pin<&mut F>) -> Poll<F::Output> {
+    // Safety: the vtable does nothing, so the contract of RawWaker is upheld
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    fut.poll(&mut Context::from_waker(&waker))
+}
+
+/// Run the future to completion by polling it in a loop, enough for the drivers used with the mocks of this module
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    loop {
+        if let Poll::Ready(val) = poll_once(fut.as_mut()) {
+            return val;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_hal::i2c::I2c;
+
+    use super::*;
+
+    type Bus = RecordingI2c<32>;
+
+    fn read(bus: &mut Bus, register: u8) -> u16 {
+        let mut buff = [0; 2];
+        bus.write_read(0x48, &[register], &mut buff).unwrap();
+        u16::from_be_bytes(buff)
+    }
+
+    fn write(bus: &mut Bus, register: u8, value: u16) {
+        let [hi, lo] = value.to_be_bytes();
+        bus.write(0x48, &[register, hi, lo]).unwrap();
+    }
+
+    #[test]
+    fn records_transactions() {
+        let mut bus = Bus::new();
+        write(&mut bus, 0x02, 0x1234);
+        assert_eq!(read(&mut bus, 0x02), 0x1234);
+        assert_eq!(
+            bus.transactions(),
+            &[
+                Transaction::Write {
+                    addr: 0x48,
+                    register: 0x02,
+                    value: 0x1234
+                },
+                Transaction::Read {
+                    addr: 0x48,
+                    register: 0x02,
+                    value: 0x1234
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn config_read_clears_data_ready_and_alerts() {
+        let mut bus = Bus::new();
+        bus.registers[CONFIGURATION] = 0x0400 | DATA_READY | HIGH_ALERT | LOW_ALERT;
+        let first = read(&mut bus, 0x01);
+        assert_eq!(
+            first & (DATA_READY | HIGH_ALERT | LOW_ALERT),
+            DATA_READY | HIGH_ALERT | LOW_ALERT
+        );
+        assert_eq!(
+            read(&mut bus, 0x01) & (DATA_READY | HIGH_ALERT | LOW_ALERT),
+            0
+        );
+    }
+
+    #[test]
+    fn config_read_keeps_thermal_alert() {
+        let mut bus = Bus::new();
+        bus.registers[CONFIGURATION] = 0x0400 | THERMAL | DATA_READY | HIGH_ALERT;
+        read(&mut bus, 0x01);
+        assert_eq!(read(&mut bus, 0x01) & (DATA_READY | HIGH_ALERT), HIGH_ALERT);
+    }
+
+    #[test]
+    fn temperature_read_clears_data_ready_only() {
+        let mut bus = Bus::new();
+        bus.registers[CONFIGURATION] = 0x0400 | DATA_READY | HIGH_ALERT;
+        read(&mut bus, 0x00);
+        assert_eq!(
+            bus.registers[CONFIGURATION] & (DATA_READY | HIGH_ALERT),
+            HIGH_ALERT
+        );
+    }
+
+    #[test]
+    fn oneshot_converts_and_shuts_down() {
+        let mut bus = Bus::new();
+        bus.temperature = 0x0C80;
+        write(&mut bus, 0x01, 0x0C00);
+        let config = read(&mut bus, 0x01);
+        assert_eq!(config & MODE_MASK, SHUTDOWN << MODE_SHIFT);
+        assert_ne!(config & DATA_READY, 0);
+        assert_eq!(read(&mut bus, 0x00), 0x0C80);
+    }
+
+    #[test]
+    fn continuous_converts_every_polls() {
+        let mut bus = Bus::new();
+        bus.conversion_polls = 2;
+        let ready: [bool; 6] = core::array::from_fn(|_| read(&mut bus, 0x01) & DATA_READY != 0);
+        assert_eq!(ready, [false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn unlocked_write_programs_eeprom_and_reset_reloads() {
+        let mut bus = Bus::new();
+        bus.program_polls = 2;
+        write(&mut bus, 0x04, EEPROM_UNLOCK);
+        write(&mut bus, 0x06, 0xBEEF);
+        assert_eq!(read(&mut bus, 0x04), EEPROM_UNLOCK | EEPROM_REGISTER_BUSY);
+        assert_ne!(read(&mut bus, 0x01) & EEPROM_BUSY, 0);
+        assert_ne!(read(&mut bus, 0x01) & EEPROM_BUSY, 0);
+        assert_eq!(read(&mut bus, 0x01) & EEPROM_BUSY, 0);
+        write(&mut bus, 0x04, 0);
+
+        write(&mut bus, 0x06, 0x1234);
+        assert_eq!(bus.eeprom[6], 0xBEEF);
+        write(&mut bus, 0x01, RESET);
+        assert_eq!(read(&mut bus, 0x06), 0xBEEF);
+        assert_eq!(read(&mut bus, 0x00), RESET_TEMPERATURE);
+    }
+
+    #[test]
+    fn queued_read_and_injected_error() {
+        let mut bus = Bus::new();
+        bus.queue_read(0x01, 0x0800);
+        assert_eq!(read(&mut bus, 0x01), 0x0800);
+        assert_eq!(read(&mut bus, 0x01) & MODE_MASK, 0);
+
+        bus.fail_next(ErrorKind::Bus);
+        let mut buff = [0; 2];
+        assert_eq!(
+            bus.write_read(0x48, &[0x00], &mut buff),
+            Err(ErrorKind::Bus)
+        );
+        assert_eq!(bus.reads(0x01), 2);
+        assert_eq!(bus.reads(0x00), 0);
+    }
+}
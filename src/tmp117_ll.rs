@@ -5,12 +5,35 @@ use device_register::{Register, RegisterInterface};
 use embedded_hal::i2c::{I2c, SevenBitAddress};
 
 use crate::error::ErrorLL;
-use crate::register::Address;
+use crate::register::{Address, Temperature};
+
+/// Callbacks invoked around each i2c access of the low level driver,
+/// for example to enable a level shifter or wake up the bus before the transaction.
+/// The `()` hook does nothing and is optimized away
+pub trait TransactionHook {
+    /// Called before each i2c transaction
+    fn pre_transaction(&mut self) {}
+
+    /// Called after each i2c transaction, even if it failed
+    fn post_transaction(&mut self) {}
+}
+
+impl TransactionHook for () {}
 
 /// The low level driver of the TPM117. Allows to read, write and edit the registers directly via the i2c bus
-pub struct Tmp117LL<T, E> {
+///
+/// Use the [ReadRegister](device_register::ReadRegister), [WriteRegister](device_register::WriteRegister)
+/// and [EditRegister](device_register::EditRegister) traits to access the registers, they only accept registers with the right permission.
+/// Writing a read only register such as [Temperature] is a compile error.
+///
+/// A [TransactionHook] can be given with [Tmp117LL::with_hook] to run code around each i2c access.
+///
+/// The bus can be borrowed instead of owned, `&mut T` implements [I2c] for any bus `T`,
+/// which allows sharing it with other drivers between uses.
+pub struct Tmp117LL<T, E, H = ()> {
     i2c: T,
     addr: u8,
+    hook: H,
     e: PhantomData<E>,
 }
 
@@ -21,38 +44,226 @@ where
 {
     /// Creates a new instace of the Tmp117 from an i2c bus
     pub fn new(i2c: T, addr: u8) -> Self {
+        Self::with_hook(i2c, addr, ())
+    }
+}
+
+impl<'a, T, E> Tmp117LL<&'a mut T, E>
+where
+    T: I2c<SevenBitAddress, Error = E>,
+    E: embedded_hal::i2c::Error,
+{
+    /// Creates a new instance borrowing the i2c bus, for a short lived driver doing a one-off access.
+    /// The bus is usable again once the driver is dropped
+    pub fn with_borrowed(i2c: &'a mut T, addr: u8) -> Self {
+        Self::new(i2c, addr)
+    }
+}
+
+impl<T, E, H> Tmp117LL<T, E, H>
+where
+    T: I2c<SevenBitAddress, Error = E>,
+    E: embedded_hal::i2c::Error,
+    H: TransactionHook,
+{
+    /// Creates a new instance of the Tmp117 from an i2c bus, calling the hook around each i2c access
+    pub fn with_hook(i2c: T, addr: u8, hook: H) -> Self {
         Self {
             i2c,
             addr,
+            hook,
             e: PhantomData,
         }
     }
+
+    /// Change the i2c address of the device accessed, to reuse the driver for another device behind a multiplexer for example.
+    /// The driver does not switch the multiplexer channel and keeps no state per device, switch it before the next access
+    pub fn set_address(&mut self, addr: u8) {
+        self.addr = addr;
+    }
+
+    /// Returns the hook, to inspect or change its state
+    pub fn hook_mut(&mut self) -> &mut H {
+        &mut self.hook
+    }
+
+    fn write_read(&mut self, address: Address, buff: &mut [u8; 2]) -> Result<(), ErrorLL<E>> {
+        self.hook.pre_transaction();
+        let res = self.i2c.write_read(self.addr, &[address.0], buff);
+        self.hook.post_transaction();
+        res.map_err(ErrorLL::Bus)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), ErrorLL<E>> {
+        self.hook.pre_transaction();
+        let res = self.i2c.write(self.addr, bytes);
+        self.hook.post_transaction();
+        res.map_err(ErrorLL::Bus)
+    }
+
+    /// Reads the raw value of the register at the address, without decoding it
+    pub fn read_raw(&mut self, address: Address) -> Result<u16, ErrorLL<E>> {
+        let mut buff = [0; 2];
+        self.write_read(address, &mut buff)?;
+        Ok(u16::from_be_bytes(buff))
+    }
+
+    /// Reads the temperature register as a sign extended value, one lsb is [CELCIUS_CONVERSION](crate::CELCIUS_CONVERSION).
+    /// Does a single transaction without going through the generic register decoding, for the hot path
+    pub fn read_temperature_raw(&mut self) -> Result<i16, ErrorLL<E>> {
+        let val = self.read_raw(Temperature::ADDRESS)?;
+        Ok(val as i16)
+    }
 }
 
-impl<T, E, R> RegisterInterface<R, Address> for Tmp117LL<T, E>
+/// Encodes the bytes sent on the bus when writing a register,
+/// the address of the register followed by the value in big endian.
+/// For example, writing `0x6000` to the [HighLimit](crate::register::HighLimit) register sends `[0x02, 0x60, 0x00]`.
+/// Both drivers use it, so the framing is the same for sync and async writes
+pub fn encode_write<R>(register: &R) -> [u8; 3]
+where
+    R: Register<Address = Address> + Clone,
+    u16: From<R>,
+{
+    let val: u16 = register.clone().into();
+    let packet = val.to_be_bytes();
+    [R::ADDRESS.0, packet[0], packet[1]]
+}
+
+impl<T, E, H, R> RegisterInterface<R, Address> for Tmp117LL<T, E, H>
 where
     R: Register<Address = Address> + Clone + TryFrom<u16>,
     u16: From<R>,
     E: embedded_hal::i2c::Error,
     T: embedded_hal::i2c::I2c + embedded_hal::i2c::ErrorType<Error = E>,
+    H: TransactionHook,
 {
     type Error = ErrorLL<E>;
 
     fn read_register(&mut self) -> Result<R, Self::Error> {
         let mut buff = [0; 2];
-        self.i2c
-            .write_read(self.addr, &[R::ADDRESS.0], &mut buff)
-            .map_err(ErrorLL::Bus)?;
-        let val = u16::from_be_bytes(buff[0..2].try_into().unwrap());
+        self.write_read(R::ADDRESS, &mut buff)?;
+        let val = u16::from_be_bytes(buff);
         R::try_from(val).map_err(|_| ErrorLL::InvalidData)
     }
 
     fn write_register(&mut self, register: &R) -> Result<(), Self::Error> {
-        let val: u16 = register.clone().into();
-        let packet = val.to_be_bytes();
+        self.write(&encode_write(register))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use device_register::{ReadRegister, WriteRegister};
+
+    use super::*;
+    use crate::register::{Configuration, DeviceID, HighLimit, TemperatureOffset};
+    use crate::test_util::{RecordingI2c, Transaction};
+
+    #[test]
+    fn encode_write_bytes() {
+        assert_eq!(encode_write(&HighLimit::from(0x6000)), [0x02, 0x60, 0x00]);
+        assert_eq!(
+            encode_write(&Configuration::try_from(0x0220).unwrap()),
+            [0x01, 0x02, 0x20]
+        );
+        assert_eq!(
+            encode_write(&TemperatureOffset::from(0xFF80)),
+            [0x08, 0xFF, 0x80]
+        );
+    }
+
+    #[test]
+    fn read_temperature_raw_single_transaction() {
+        let mut bus = RecordingI2c::<4>::new();
+        bus.registers[0] = 0xEC00;
+        let mut ll = Tmp117LL::new(&mut bus, 0x48);
+        assert_eq!(ll.read_temperature_raw(), Ok(-5120));
+        assert_eq!(
+            bus.transactions(),
+            &[Transaction::Read {
+                addr: 0x48,
+                register: 0x00,
+                value: 0xEC00
+            }]
+        );
+    }
+
+    #[test]
+    fn read_register_with_borrowed_bus() {
+        let mut bus = RecordingI2c::<4>::new();
+        let mut ll = Tmp117LL::new(&mut bus, 0x49);
+        let id: DeviceID = ll.read().unwrap();
+        assert_eq!(u16::from(id), 0x1117);
+
+        // The borrow ended with the driver, the bus can be used again
+        bus.clear();
+        let mut ll = Tmp117LL::new(&mut bus, 0x48);
+        let id: DeviceID = ll.read().unwrap();
+        assert_eq!(u16::from(id), 0x1117);
+        assert_eq!(
+            bus.transactions(),
+            &[Transaction::Read {
+                addr: 0x48,
+                register: 0x0F,
+                value: 0x1117
+            }]
+        );
+    }
+
+    #[test]
+    fn reads_exactly_two_bytes() {
+        // The mock panics on a read of any other length
+        let mut bus = RecordingI2c::<4>::new();
+        bus.registers[8] = 0x1234;
+        let mut ll = Tmp117LL::new(&mut bus, 0x48);
+        let offset: TemperatureOffset = ll.read().unwrap();
+        assert_eq!(u16::from(offset), 0x1234);
+        assert_eq!(ll.read_raw(Address(0x08)), Ok(0x1234));
+        assert_eq!(bus.reads(0x08), 2);
+    }
+
+    #[test]
+    fn set_address_retargets_reads() {
+        let mut bus = RecordingI2c::<4>::new();
+        bus.registers[0] = 0x0C80;
+        let mut ll = Tmp117LL::new(&mut bus, 0x48);
+        ll.read_temperature_raw().unwrap();
+        ll.set_address(0x4A);
+        ll.read_temperature_raw().unwrap();
+        let addrs: [u8; 2] = core::array::from_fn(|i| match bus.transactions()[i] {
+            Transaction::Read { addr, .. } | Transaction::Write { addr, .. } => addr,
+        });
+        assert_eq!(addrs, [0x48, 0x4A]);
+    }
+
+    #[test]
+    fn write_high_limit_big_endian() {
+        // The mock decodes the written bytes as the address followed by the big endian value
+        let mut bus = RecordingI2c::<4>::new();
+        bus.registers[2] = 0;
+        let mut ll = Tmp117LL::new(&mut bus, 0x48);
+        WriteRegister::write(&mut ll, HighLimit::from(0x6000)).unwrap();
+        assert_eq!(
+            bus.transactions(),
+            &[Transaction::Write {
+                addr: 0x48,
+                register: 0x02,
+                value: 0x6000
+            }]
+        );
+        assert_eq!(bus.registers[2], 0x6000);
+    }
 
-        self.i2c
-            .write(self.addr, &[R::ADDRESS.0, packet[0], packet[1]])
-            .map_err(ErrorLL::Bus)
+    #[test]
+    fn with_borrowed_in_block() {
+        let mut bus = RecordingI2c::<4>::new();
+        bus.registers[0] = 0x0C80;
+        let raw = {
+            let mut ll = Tmp117LL::with_borrowed(&mut bus, 0x48);
+            ll.read_temperature_raw().unwrap()
+        };
+        assert_eq!(raw, 0x0C80);
+        assert_eq!(bus.reads(0x00), 1);
     }
 }